@@ -51,14 +51,7 @@ fn main() -> Result<(), MainError> {
                 .unwrap_or("unknown weapon");
 
             let shooter = state
-                .players
-                .iter()
-                .find(|player| {
-                    player
-                        .weapons
-                        .iter()
-                        .any(|weapon| collision.projectile.launcher == *weapon)
-                })
+                .get_weapon_owner(collision.projectile.launcher)
                 .and_then(|player| player.info.as_ref());
 
             if let Some(shooter) = shooter {
@@ -0,0 +1,31 @@
+use std::env;
+use std::fs;
+
+use main_error::MainError;
+use tf_demo_parser::demo::merge::merge_demos;
+use tf_demo_parser::Demo;
+
+fn main() -> Result<(), MainError> {
+    #[cfg(feature = "trace")]
+    tracing_subscriber::fmt::init();
+
+    #[cfg(feature = "better_panic")]
+    better_panic::install();
+
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 4 {
+        println!("usage: merge_demo <output> <input> <input>...");
+        return Ok(());
+    }
+    let out_path = args[1].clone();
+    let files = args[2..]
+        .iter()
+        .map(fs::read)
+        .collect::<Result<Vec<_>, _>>()?;
+    let demos: Vec<_> = files.iter().map(|file| Demo::new(file)).collect();
+
+    let merged = merge_demos(&demos)?;
+    fs::write(out_path, merged)?;
+
+    Ok(())
+}
@@ -1,12 +1,12 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
 
 use main_error::MainError;
-pub use tf_demo_parser::{Demo, DemoParser, Parse, ParserState};
-use tf_demo_parser::demo::packet::stringtable::{StringTableEntry};
+use tf_demo_parser::demo::data::stringtables::StringTables;
+use tf_demo_parser::demo::packet::stringtable::StringTableEntry;
 use tf_demo_parser::demo::parser::MessageHandler;
 use tf_demo_parser::MessageType;
+pub use tf_demo_parser::{Demo, DemoParser, Parse, ParserState};
 
 #[cfg(feature = "jemallocator")]
 #[global_allocator]
@@ -31,19 +31,19 @@ fn main() -> Result<(), MainError> {
     let (_, tables) = parser.parse()?;
     if let Some(name) = args.get(2) {
         if name == "--tables" {
-            for (name, _) in tables {
-                println!("{name}");
+            for table in tables.tables() {
+                println!("{}", table.name);
             }
         } else {
             let table = tables.get(name).expect("table not found");
-            for item in table {
+            for item in table.entries.iter().flatten() {
                 println!("{item}");
             }
         }
     } else {
-        for (name, table) in tables {
-            println!("{name}:");
-            for item in table {
+        for table in tables.tables() {
+            println!("{}:", table.name);
+            for item in table.entries.iter().flatten() {
                 println!("\t{item}");
             }
         }
@@ -51,29 +51,31 @@ fn main() -> Result<(), MainError> {
     Ok(())
 }
 
+/// Collects all string tables, the parser state only keeps the precache tables
 #[derive(Default)]
 struct StringTableHandler {
-    pub tables: HashMap<String, Vec<String>>,
+    tables: StringTables,
 }
 
 impl MessageHandler for StringTableHandler {
-    type Output = HashMap<String, Vec<String>>;
+    type Output = StringTables;
 
-    fn does_handle(message_type: MessageType) -> bool {
-        matches!(message_type, MessageType::CreateStringTable | MessageType::UpdateStringTable)
+    fn does_handle(_message_type: MessageType) -> bool {
+        false
     }
 
-    fn handle_string_entry(&mut self, table: &str, index: usize, entries: &StringTableEntry, _parser_state: &ParserState) {
-        let table = self.tables.entry(table.into()).or_default();
-        if index < table.len() {
-            table[index] = entries.text().into();
-        } else {
-            table.insert(index, entries.text().into());
-        }
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        self.tables
+            .insert(table, index, entry.text.as_deref(), parser_state.tick);
     }
 
-
     fn into_output(self, _state: &ParserState) -> Self::Output {
         self.tables
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,45 @@
+use std::env;
+use std::fs;
+
+use main_error::MainError;
+use tf_demo_parser::demo::strip::{strip_demo, StripOptions};
+use tf_demo_parser::Demo;
+
+fn main() -> Result<(), MainError> {
+    #[cfg(feature = "trace")]
+    tracing_subscriber::fmt::init();
+
+    #[cfg(feature = "better_panic")]
+    better_panic::install();
+
+    let args: Vec<_> = env::args().skip(1).collect();
+    let (flags, paths): (Vec<_>, Vec<_>) = args.iter().partition(|arg| arg.starts_with("--"));
+    if paths.len() != 2 {
+        println!("usage: strip_demo [--voice] [--team-chat] [--console] <input> <output>");
+        return Ok(());
+    }
+
+    let mut options = StripOptions::default();
+    for flag in flags {
+        match flag.as_str() {
+            "--voice" => options.voice = true,
+            "--team-chat" => options.chat = StripOptions::TEAM_CHAT.to_vec(),
+            "--console" => options.console = true,
+            _ => {
+                println!("unknown option {flag}");
+                return Ok(());
+            }
+        }
+    }
+    // strip everything when no specific data is selected
+    if options == StripOptions::default() {
+        options = StripOptions::all();
+    }
+
+    let file = fs::read(paths[0])?;
+    let demo = Demo::new(&file);
+    let stripped = strip_demo(&demo, &options)?;
+    fs::write(paths[1], stripped)?;
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use std::env;
+use std::fs;
+
+use main_error::MainError;
+use tf_demo_parser::demo::verify::verify_roundtrip;
+use tf_demo_parser::Demo;
+
+fn main() -> Result<(), MainError> {
+    #[cfg(feature = "trace")]
+    tracing_subscriber::fmt::init();
+
+    #[cfg(feature = "better_panic")]
+    better_panic::install();
+
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 2 {
+        println!("1 argument required");
+        return Ok(());
+    }
+    let path = args[1].clone();
+    let file = fs::read(path)?;
+    let demo = Demo::new(&file);
+
+    let packets = verify_roundtrip(&demo)?;
+    println!("All {packets} packets encode and decode to the same data");
+
+    Ok(())
+}
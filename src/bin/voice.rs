@@ -0,0 +1,48 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use main_error::MainError;
+use tf_demo_parser::demo::parser::voiceanalyser::VoiceAnalyser;
+use tf_demo_parser::{Demo, DemoParser};
+
+fn main() -> Result<(), MainError> {
+    #[cfg(feature = "trace")]
+    tracing_subscriber::fmt::init();
+
+    #[cfg(feature = "better_panic")]
+    better_panic::install();
+
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 3 {
+        println!("usage: export_voice <input> <output directory>");
+        return Ok(());
+    }
+    let file = fs::read(&args[1])?;
+    let demo = Demo::new(&file);
+    let (_, voice) =
+        DemoParser::new_with_analyser(demo.get_stream(), VoiceAnalyser::new()).parse()?;
+
+    if voice.codec != "steam" {
+        println!("unsupported voice codec {}", voice.codec);
+        return Ok(());
+    }
+
+    let out_dir = Path::new(&args[2]);
+    fs::create_dir_all(out_dir)?;
+    for (index, speaker) in voice.speakers.iter().enumerate() {
+        let name = match speaker.steam_id() {
+            Some(steam_id) => format!("{index}_{steam_id}.ogg"),
+            None => format!("{index}_entity_{}.ogg", speaker.entity_id),
+        };
+        let file = fs::File::create(out_dir.join(&name))?;
+        speaker.write_ogg(voice.interval_per_tick, std::io::BufWriter::new(file))?;
+        println!("{name}: {} voice packets", speaker.frames.len());
+        let silk = speaker.silk_payloads();
+        if silk > 0 {
+            println!("{name}: skipped {silk} SILK payloads");
+        }
+    }
+
+    Ok(())
+}
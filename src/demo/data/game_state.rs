@@ -4,12 +4,62 @@ use crate::demo::message::packetentities::EntityId;
 use crate::demo::packet::datatable::{ClassId, ServerClass, ServerClassName};
 use crate::demo::parser::analyser::{Class, Team, UserId, UserInfo};
 use crate::demo::vector::Vector;
+use enumflags2::{bitflags, BitFlags};
 use parse_display::Display;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
-#[derive(Default, Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Hash, Display)]
-pub struct Handle(pub i64);
+const HANDLE_INDEX_BITS: u32 = 11;
+const HANDLE_SERIAL_BITS: u32 = 10;
+
+/// A networked entity handle (`EHANDLE`)
+///
+/// The lower 11 bits contain the entity index, the next 10 bits the serial number of the entity,
+/// the serial number is used to detect handles that point to an entity index that has been re-used.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Hash, Display)]
+pub struct Handle(u32);
+
+impl Handle {
+    /// The handle value networked for handles that don't point to any entity
+    pub const INVALID: Handle = Handle((1 << (HANDLE_INDEX_BITS + HANDLE_SERIAL_BITS)) - 1);
+
+    pub fn new(index: EntityId, serial: u16) -> Self {
+        let index = u32::from(index) & ((1 << HANDLE_INDEX_BITS) - 1);
+        let serial = serial as u32 & ((1 << HANDLE_SERIAL_BITS) - 1);
+        Handle(index | (serial << HANDLE_INDEX_BITS))
+    }
+
+    pub fn is_valid(self) -> bool {
+        self != Handle::INVALID
+    }
+
+    /// The entity index the handle points to
+    pub fn index(self) -> Option<EntityId> {
+        self.is_valid()
+            .then(|| EntityId::from(self.0 & ((1 << HANDLE_INDEX_BITS) - 1)))
+    }
+
+    pub fn serial(self) -> u16 {
+        (self.0 >> HANDLE_INDEX_BITS) as u16
+    }
+
+    pub fn raw(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Handle {
+    fn default() -> Self {
+        Handle::INVALID
+    }
+}
+
+impl From<i64> for Handle {
+    fn from(raw: i64) -> Self {
+        Handle(raw as u32 & Handle::INVALID.0)
+    }
+}
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
 pub enum PlayerState {
@@ -52,6 +102,278 @@ impl Box {
     }
 }
 
+/// The TF2 player conditions (`ETFCond`)
+///
+/// Only the first 128 conditions fit in the flag set, conditions from `m_nPlayerCondEx4` and up
+/// are ignored
+#[bitflags]
+#[repr(u128)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerCondition {
+    Aiming = 1 << 0,
+    Zoomed = 1 << 1,
+    Disguising = 1 << 2,
+    Disguised = 1 << 3,
+    /// Cloaked spy
+    Stealthed = 1 << 4,
+    /// Ubercharged
+    Invulnerable = 1 << 5,
+    Teleported = 1 << 6,
+    Taunting = 1 << 7,
+    InvulnerableWearingOff = 1 << 8,
+    StealthedBlink = 1 << 9,
+    SelectedToTeleport = 1 << 10,
+    /// Kritzkrieg or other crit boost
+    CritBoosted = 1 << 11,
+    TmpDamageBonus = 1 << 12,
+    FeignDeath = 1 << 13,
+    Phase = 1 << 14,
+    Stunned = 1 << 15,
+    /// Buff banner
+    OffenseBuff = 1 << 16,
+    ShieldCharge = 1 << 17,
+    DemoBuff = 1 << 18,
+    /// Mini-crits from the crit-a-cola
+    EnergyBuff = 1 << 19,
+    RadiusHeal = 1 << 20,
+    HealthBuff = 1 << 21,
+    /// On fire
+    Burning = 1 << 22,
+    HealthOverhealed = 1 << 23,
+    /// Covered in jarate
+    Urine = 1 << 24,
+    Bleeding = 1 << 25,
+    /// Battalion's backup
+    DefenseBuff = 1 << 26,
+    /// Covered in mad milk
+    MadMilk = 1 << 27,
+    /// Quick-fix uber
+    MegaHeal = 1 << 28,
+    /// Concheror
+    RegenOnDamageBuff = 1 << 29,
+    MarkedForDeath = 1 << 30,
+    NoHealingDamageBuff = 1 << 31,
+    SpeedBoost = 1 << 32,
+    CritBoostedPumpkin = 1 << 33,
+    CritBoostedUserBuff = 1 << 34,
+    CritBoostedDemoCharge = 1 << 35,
+    SodaPopperHype = 1 << 36,
+    CritBoostedFirstBlood = 1 << 37,
+    CritBoostedBonusTime = 1 << 38,
+    CritBoostedCtfCapture = 1 << 39,
+    CritBoostedOnKill = 1 << 40,
+    CannotSwitchFromMelee = 1 << 41,
+    DefenseBuffNoCritBlock = 1 << 42,
+    Reprogrammed = 1 << 43,
+    CritBoostedRageBuff = 1 << 44,
+    DefenseBuffHigh = 1 << 45,
+    SniperChargeRageBuff = 1 << 46,
+    DisguiseWearingOff = 1 << 47,
+    MarkedForDeathSilent = 1 << 48,
+    DisguisedAsDispenser = 1 << 49,
+    Sapped = 1 << 50,
+    InvulnerableHideUnlessDamaged = 1 << 51,
+    InvulnerableUserBuff = 1 << 52,
+    HalloweenBombHead = 1 << 53,
+    HalloweenThriller = 1 << 54,
+    RadiusHealOnDamage = 1 << 55,
+    CritBoostedCardEffect = 1 << 56,
+    InvulnerableCardEffect = 1 << 57,
+    MedigunUberBulletResist = 1 << 58,
+    MedigunUberBlastResist = 1 << 59,
+    MedigunUberFireResist = 1 << 60,
+    MedigunSmallBulletResist = 1 << 61,
+    MedigunSmallBlastResist = 1 << 62,
+    MedigunSmallFireResist = 1 << 63,
+    StealthedUserBuff = 1 << 64,
+    MedigunDebuff = 1 << 65,
+    StealthedUserBuffFading = 1 << 66,
+    BulletImmune = 1 << 67,
+    BlastImmune = 1 << 68,
+    FireImmune = 1 << 69,
+    PreventDeath = 1 << 70,
+    MvmBotStunRadiowave = 1 << 71,
+    HalloweenSpeedBoost = 1 << 72,
+    HalloweenQuickHeal = 1 << 73,
+    HalloweenGiant = 1 << 74,
+    HalloweenTiny = 1 << 75,
+    HalloweenInHell = 1 << 76,
+    HalloweenGhostMode = 1 << 77,
+    MiniCritBoostedOnKill = 1 << 78,
+    ObscuredSmoke = 1 << 79,
+    ParachuteActive = 1 << 80,
+    BlastJumping = 1 << 81,
+    HalloweenKart = 1 << 82,
+    HalloweenKartDash = 1 << 83,
+    BalloonHead = 1 << 84,
+    MeleeOnly = 1 << 85,
+    SwimmingCurse = 1 << 86,
+    FreezeInput = 1 << 87,
+    HalloweenKartCage = 1 << 88,
+    DoNotUse0 = 1 << 89,
+    /// Mannpower strength rune
+    RuneStrength = 1 << 90,
+    RuneHaste = 1 << 91,
+    RuneRegen = 1 << 92,
+    RuneResist = 1 << 93,
+    RuneVampire = 1 << 94,
+    RuneReflect = 1 << 95,
+    RunePrecision = 1 << 96,
+    RuneAgility = 1 << 97,
+    GrapplingHook = 1 << 98,
+    GrapplingHookSafeFall = 1 << 99,
+    GrapplingHookLatched = 1 << 100,
+    GrapplingHookBleeding = 1 << 101,
+    AfterburnImmune = 1 << 102,
+    RuneKnockout = 1 << 103,
+    RuneImbalance = 1 << 104,
+    /// Crit boost from the Mannpower crit powerup
+    CritBoostedRuneTemp = 1 << 105,
+    PasstimeInterception = 1 << 106,
+    SwimmingNoEffects = 1 << 107,
+    Purgatory = 1 << 108,
+    RuneKing = 1 << 109,
+    RunePlague = 1 << 110,
+    RuneSupernova = 1 << 111,
+    Plague = 1 << 112,
+    KingBuffed = 1 << 113,
+    TeamGlows = 1 << 114,
+    KnockedIntoAir = 1 << 115,
+    CompetitiveWinner = 1 << 116,
+    CompetitiveLoser = 1 << 117,
+    HealingDebuff = 1 << 118,
+    PasstimePenaltyDebuff = 1 << 119,
+    GrappledToPlayer = 1 << 120,
+    GrappledByPlayer = 1 << 121,
+    ParachuteDeployed = 1 << 122,
+    /// Covered in gas from the Gas Passer
+    Gas = 1 << 123,
+    /// On fire from the Dragon's Fury
+    BurningPyro = 1 << 124,
+    RocketPack = 1 << 125,
+    LostFooting = 1 << 126,
+    AirCurrent = 1 << 127,
+}
+
+// enumflags2 can't serialize 128 bit flags, so the conditions are serialized as a list instead
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "Vec<PlayerCondition>", from = "Vec<PlayerCondition>")]
+pub struct PlayerConditions(BitFlags<PlayerCondition>);
+
+impl PlayerConditions {
+    pub fn contains(self, condition: PlayerCondition) -> bool {
+        self.0.contains(condition)
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = PlayerCondition> {
+        self.0.iter()
+    }
+
+    /// Replace one of the 32 bit words the conditions are networked in
+    ///
+    /// Word 0 is `m_nPlayerCond`, word 1 `m_nPlayerCondEx`, etc.
+    pub fn set_word(&mut self, word: usize, bits: u32) {
+        if word >= 4 {
+            return;
+        }
+        let shift = word * 32;
+        let mask = (u32::MAX as u128) << shift;
+        let bits = (self.0.bits() & !mask) | ((bits as u128) << shift);
+        self.0 = BitFlags::from_bits_truncate(bits);
+    }
+
+    /// The conditions that are set in `self` but not in `other`
+    pub fn difference(self, other: PlayerConditions) -> PlayerConditions {
+        PlayerConditions(self.0 & !other.0)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_ubered(self) -> bool {
+        self.0.intersects(
+            PlayerCondition::Invulnerable
+                | PlayerCondition::InvulnerableUserBuff
+                | PlayerCondition::InvulnerableCardEffect,
+        )
+    }
+
+    pub fn is_crit_boosted(self) -> bool {
+        self.0.intersects(
+            PlayerCondition::CritBoosted
+                | PlayerCondition::CritBoostedPumpkin
+                | PlayerCondition::CritBoostedUserBuff
+                | PlayerCondition::CritBoostedDemoCharge
+                | PlayerCondition::CritBoostedFirstBlood
+                | PlayerCondition::CritBoostedBonusTime
+                | PlayerCondition::CritBoostedCtfCapture
+                | PlayerCondition::CritBoostedOnKill
+                | PlayerCondition::CritBoostedRageBuff
+                | PlayerCondition::CritBoostedCardEffect
+                | PlayerCondition::CritBoostedRuneTemp,
+        )
+    }
+
+    pub fn is_cloaked(self) -> bool {
+        self.0
+            .intersects(PlayerCondition::Stealthed | PlayerCondition::StealthedUserBuff)
+    }
+
+    pub fn is_disguised(self) -> bool {
+        self.contains(PlayerCondition::Disguised)
+    }
+
+    pub fn is_burning(self) -> bool {
+        self.0
+            .intersects(PlayerCondition::Burning | PlayerCondition::BurningPyro)
+    }
+
+    pub fn is_bleeding(self) -> bool {
+        self.contains(PlayerCondition::Bleeding)
+    }
+
+    pub fn is_jarated(self) -> bool {
+        self.contains(PlayerCondition::Urine)
+    }
+
+    pub fn is_milked(self) -> bool {
+        self.contains(PlayerCondition::MadMilk)
+    }
+
+    pub fn is_marked_for_death(self) -> bool {
+        self.0
+            .intersects(PlayerCondition::MarkedForDeath | PlayerCondition::MarkedForDeathSilent)
+    }
+}
+
+impl From<Vec<PlayerCondition>> for PlayerConditions {
+    fn from(conditions: Vec<PlayerCondition>) -> Self {
+        PlayerConditions(conditions.into_iter().collect())
+    }
+}
+
+impl From<PlayerConditions> for Vec<PlayerCondition> {
+    fn from(conditions: PlayerConditions) -> Self {
+        conditions.iter().collect()
+    }
+}
+
+impl From<PlayerCondition> for PlayerConditions {
+    fn from(condition: PlayerCondition) -> Self {
+        PlayerConditions(condition.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ConditionChange {
+    pub tick: DemoTick,
+    pub player: EntityId,
+    pub condition: PlayerCondition,
+    /// Whether the condition was added or removed
+    pub active: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Player {
     pub entity: EntityId,
@@ -69,7 +391,13 @@ pub struct Player {
     pub ping: u16,
     pub in_pvs: bool,
     pub bounds: Box,
-    pub weapons: [Handle; 3],
+    /// Handles of the weapons held by the player by their index in the `m_hMyWeapons` array,
+    /// empty slots are left out, see [`GameState::loadout`] for the weapons themselves
+    pub(crate) weapons: BTreeMap<usize, Handle>,
+    pub active_weapon: Handle,
+    /// Reserve ammo by ammo type, only networked for the player that recorded the demo
+    pub ammo: Vec<u32>,
+    pub conditions: PlayerConditions,
 }
 
 pub const PLAYER_BOX_DEFAULT: Box = Box {
@@ -94,6 +422,11 @@ impl Player {
         }
     }
 
+    /// Reserve ammo the player has for a weapon
+    pub fn reserve_ammo(&self, weapon: &Weapon) -> Option<u32> {
+        self.ammo.get(usize::from(weapon.ammo_type?)).copied()
+    }
+
     pub fn collides(&self, projectile: &Projectile, time_per_tick: f32) -> bool {
         let current_position = projectile.position;
         let next_position = projectile.position + (projectile.initial_speed * time_per_tick);
@@ -110,7 +443,7 @@ impl Player {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Sentry {
     pub entity: EntityId,
-    pub builder: UserId,
+    pub builder: Handle,
     pub position: Vector,
     pub level: u8,
     pub max_health: u16,
@@ -120,7 +453,7 @@ pub struct Sentry {
     pub team: Team,
     pub angle: f32,
     pub player_controlled: bool,
-    pub auto_aim_target: UserId,
+    pub auto_aim_target: Handle,
     pub shells: u16,
     pub rockets: u16,
     pub is_mini: bool,
@@ -129,7 +462,7 @@ pub struct Sentry {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Dispenser {
     pub entity: EntityId,
-    pub builder: UserId,
+    pub builder: Handle,
     pub position: Vector,
     pub level: u8,
     pub max_health: u16,
@@ -145,7 +478,7 @@ pub struct Dispenser {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Teleporter {
     pub entity: EntityId,
-    pub builder: UserId,
+    pub builder: Handle,
     pub position: Vector,
     pub level: u8,
     pub max_health: u16,
@@ -211,7 +544,7 @@ impl Building {
         }
     }
 
-    pub fn builder(&self) -> UserId {
+    pub fn builder(&self) -> Handle {
         match self {
             Building::Sentry(Sentry { builder, .. })
             | Building::Dispenser(Dispenser { builder, .. })
@@ -219,6 +552,12 @@ impl Building {
         }
     }
 
+    /// The user id of the player that built the building, if the builder is still connected
+    pub fn builder_user_id(&self, state: &GameState) -> Option<UserId> {
+        let player = state.get_player_by_handle(self.builder())?;
+        player.info.as_ref().map(|info| info.user_id)
+    }
+
     pub fn angle(&self) -> f32 {
         match self {
             Building::Sentry(Sentry { angle, .. })
@@ -274,6 +613,207 @@ pub enum BuildingClass {
     Teleporter,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MedigunType {
+    #[default]
+    Stock,
+    Kritzkrieg,
+    QuickFix,
+    Vaccinator,
+}
+
+impl MedigunType {
+    /// Get the medigun type from the item definition index, reskins of the stock medigun all
+    /// behave like the stock medigun
+    pub fn new(item_index: i64) -> Self {
+        match item_index {
+            35 => MedigunType::Kritzkrieg,
+            411 => MedigunType::QuickFix,
+            998 => MedigunType::Vaccinator,
+            _ => MedigunType::Stock,
+        }
+    }
+}
+
+/// Loadout slot of a weapon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum WeaponSlot {
+    Primary,
+    Secondary,
+    Melee,
+    /// Engineer toolbox and spy sapper
+    Building,
+    /// Engineer construction pda and spy disguise kit
+    Pda,
+    /// Engineer destruction pda and spy invisibility watch
+    Pda2,
+    /// Grappling hook, spellbook and the passtime gun
+    Action,
+    #[default]
+    Unknown,
+}
+
+impl WeaponSlot {
+    /// Get the slot from the server class of the weapon
+    ///
+    /// Weapons that are used by multiple classes in different slots are networked with a
+    /// class specific server class, except for the stock shotgun which is only a primary for
+    /// the engineer.
+    pub fn new(class_name: &str) -> Self {
+        match class_name {
+            "CTFScatterGun"
+            | "CTFSodaPopper"
+            | "CTFPEPBrawlerBlaster"
+            | "CTFPistol_ScoutPrimary"
+            | "CTFRocketLauncher"
+            | "CTFRocketLauncher_AirStrike"
+            | "CTFRocketLauncher_DirectHit"
+            | "CTFRocketLauncher_Mortar"
+            | "CTFParticleCannon"
+            | "CTFFlameThrower"
+            | "CTFWeaponFlameBall"
+            | "CTFGrenadeLauncher"
+            | "CTFCannon"
+            | "CTFParachute_Primary"
+            | "CTFMinigun"
+            | "CTFShotgun"
+            | "CTFShotgun_Revenge"
+            | "CTFShotgunBuildingRescue"
+            | "CTFDRGPomson"
+            | "CTFSyringeGun"
+            | "CTFCrossbow"
+            | "CTFSniperRifle"
+            | "CTFSniperRifleClassic"
+            | "CTFSniperRifleDecap"
+            | "CTFCompoundBow" => WeaponSlot::Primary,
+            "CTFPistol"
+            | "CTFPistol_Scout"
+            | "CTFPistol_ScoutSecondary"
+            | "CTFLunchBox"
+            | "CTFLunchBox_Drink"
+            | "CTFJar"
+            | "CTFJarMilk"
+            | "CTFJarGas"
+            | "CTFCleaver"
+            | "CTFShotgun_Soldier"
+            | "CTFShotgun_HWG"
+            | "CTFShotgun_Pyro"
+            | "CTFBuffItem"
+            | "CTFRaygun"
+            | "CTFParachute"
+            | "CTFParachute_Secondary"
+            | "CTFRocketPack"
+            | "CTFFlareGun"
+            | "CTFFlareGun_Revenge"
+            | "CTFPipebombLauncher"
+            | "CTFLaserPointer"
+            | "CTFMechanicalArm"
+            | "CWeaponMedigun"
+            | "CTFSMG"
+            | "CTFChargedSMG"
+            | "CTFRevolver" => WeaponSlot::Secondary,
+            "CTFBat" | "CTFBat_Fish" | "CTFBat_Giftwrap" | "CTFBat_Wood" | "CTFShovel"
+            | "CTFFireAxe" | "CTFSlap" | "CTFBottle" | "CTFStickBomb" | "CTFSword"
+            | "CTFKatana" | "CTFFists" | "CTFWrench" | "CTFRobotArm" | "CTFBonesaw" | "CTFClub"
+            | "CTFKnife" | "CTFBreakableMelee" | "CTFBreakableSign" => WeaponSlot::Melee,
+            "CTFWeaponBuilder" | "CTFWeaponSapper" => WeaponSlot::Building,
+            "CTFWeaponPDA" | "CTFWeaponPDA_Engineer_Build" | "CTFWeaponPDA_Spy" => WeaponSlot::Pda,
+            "CTFWeaponPDA_Engineer_Destroy" | "CTFWeaponInvis" => WeaponSlot::Pda2,
+            "CTFGrapplingHook" | "CTFSpellBook" | "CPasstimeGun" => WeaponSlot::Action,
+            _ => WeaponSlot::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Weapon {
+    pub entity: EntityId,
+    pub class: ClassId,
+    pub slot: WeaponSlot,
+    pub item_index: u16,
+    pub owner: Handle,
+    /// Ammo in the primary clip, only networked for the player that recorded the demo
+    pub clip: Option<u32>,
+    /// Ammo in the secondary clip, only networked for the player that recorded the demo
+    pub clip2: Option<u32>,
+    /// Ammo type used by the primary clip, the reserve ammo for it is stored on the player.
+    /// Only networked for the player that recorded the demo
+    pub ammo_type: Option<u8>,
+}
+
+impl Weapon {
+    pub fn new(entity: EntityId, class: ClassId, slot: WeaponSlot) -> Self {
+        Weapon {
+            entity,
+            class,
+            slot,
+            item_index: 0,
+            owner: Handle::default(),
+            clip: None,
+            clip2: None,
+            ammo_type: None,
+        }
+    }
+}
+
+/// The weapons of a player, resolved from the weapon handles of the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loadout<'a> {
+    /// Weapons in the order they are stored in the player's `m_hMyWeapons` array, this is the
+    /// order in which the player received them and not the slot order
+    pub weapons: Vec<&'a Weapon>,
+    pub active: Option<&'a Weapon>,
+}
+
+impl<'a> Loadout<'a> {
+    /// The weapon in a slot
+    pub fn weapon(&self, slot: WeaponSlot) -> Option<&'a Weapon> {
+        self.weapons
+            .iter()
+            .copied()
+            .find(|weapon| weapon.slot == slot)
+    }
+
+    /// Slot of the active weapon
+    pub fn active_slot(&self) -> Option<WeaponSlot> {
+        self.active.map(|weapon| weapon.slot)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WeaponSwitch {
+    pub tick: DemoTick,
+    pub player: EntityId,
+    /// The new active weapon, `None` if the weapon entity isn't known
+    pub weapon: Option<EntityId>,
+    pub item_index: Option<u16>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Medigun {
+    pub entity: EntityId,
+    pub ty: MedigunType,
+    pub item_index: u16,
+    pub heal_target: Handle,
+    pub healing: bool,
+    /// Charge level from 0 to 1
+    pub charge: f32,
+    /// Whether the ubercharge is currently active
+    pub charge_release: bool,
+    pub holstered: bool,
+    /// Selected resistance for the vaccinator, 0 for bullet, 1 for blast, 2 for fire
+    pub resist_type: u8,
+}
+
+impl Medigun {
+    pub fn new(entity: EntityId) -> Self {
+        Medigun {
+            entity,
+            ..Medigun::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Projectile {
     pub id: EntityId,
@@ -381,6 +921,309 @@ pub struct Collision {
     pub projectile: Projectile,
 }
 
+/// The maximum number of control points in a map
+pub const MAX_CONTROL_POINTS: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ControlPoint {
+    pub index: u8,
+    /// Name of the point, only known once a capture event for the point has been seen
+    ///
+    /// The names are not networked, the control point entities and `CTeamControlPointMaster`
+    /// only exist on the server and the objective resource doesn't include them.
+    pub name: String,
+    pub position: Vector,
+    pub owner: Team,
+    pub capping_team: Team,
+    pub team_in_zone: Team,
+    /// Capture progress of the capping team from 0 to 1
+    pub cap_progress: f32,
+    pub locked: bool,
+    pub blocked: bool,
+    pub visible: bool,
+    /// Whether the point is part of the current mini round, always set for maps without mini rounds
+    pub in_mini_round: bool,
+    pub red_can_cap: bool,
+    pub blue_can_cap: bool,
+    pub red_on_point: u8,
+    pub blue_on_point: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptureEvent {
+    pub tick: DemoTick,
+    pub point: u8,
+    pub point_name: String,
+    pub kind: CaptureEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum CaptureEventKind {
+    Start {
+        owner: Team,
+        team: Team,
+        cappers: Vec<EntityId>,
+        cap_time: f32,
+    },
+    Captured {
+        team: Team,
+        cappers: Vec<EntityId>,
+    },
+    Blocked {
+        blocker: EntityId,
+        victim: Option<EntityId>,
+    },
+}
+
+/// A payload cart, tracked through the train watcher of the cart
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PayloadCart {
+    pub watcher: EntityId,
+    pub team: Team,
+    /// Position of the `func_tracktrain` moving the cart
+    pub position: Vector,
+    /// Progress along the track from 0 to 1
+    pub progress: f32,
+    pub cappers: u8,
+    /// The speed of the cart, 0 when stopped and negative while receding
+    pub speed_level: i8,
+    /// Server time at which the cart starts receding
+    pub recede_time: f32,
+}
+
+impl PayloadCart {
+    pub fn is_receding(&self) -> bool {
+        self.speed_level < 0
+    }
+}
+
+/// A `func_tracktrain`, the entity moving a payload cart along the track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Train {
+    pub entity: EntityId,
+    pub team: Team,
+    pub position: Vector,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PayloadProgress {
+    pub tick: DemoTick,
+    pub round: u32,
+    pub watcher: EntityId,
+    pub progress: f32,
+    pub cappers: u8,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum FlagStatus {
+    #[default]
+    Home = 0,
+    Stolen = 1,
+    Dropped = 2,
+}
+
+impl FlagStatus {
+    pub fn new(number: i64) -> Self {
+        match number {
+            1 => FlagStatus::Stolen,
+            2 => FlagStatus::Dropped,
+            _ => FlagStatus::Home,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Flag {
+    pub entity: EntityId,
+    pub team: Team,
+    pub status: FlagStatus,
+    /// Position of the flag, relative to the carrier while the flag is carried
+    pub position: Vector,
+    pub carrier: Handle,
+    /// Server time at which a dropped flag returns
+    pub reset_time: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PasstimeBall {
+    pub entity: EntityId,
+    pub position: Vector,
+    pub carrier: Handle,
+    pub previous_carrier: Handle,
+    /// Number of completed passes
+    pub passes: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FlagAction {
+    Pickup,
+    Capture,
+    Defend,
+    Drop,
+    Return,
+}
+
+impl FlagAction {
+    pub fn new(number: u16) -> Option<Self> {
+        match number {
+            1 => Some(FlagAction::Pickup),
+            2 => Some(FlagAction::Capture),
+            3 => Some(FlagAction::Defend),
+            4 => Some(FlagAction::Drop),
+            5 => Some(FlagAction::Return),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObjectiveEvent {
+    pub tick: DemoTick,
+    pub kind: ObjectiveEventKind,
+}
+
+/// Events for carried objectives, players are identified by their entity id
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ObjectiveEventKind {
+    Flag {
+        player: EntityId,
+        /// Team of the flag
+        team: Team,
+        action: FlagAction,
+    },
+    BallPickup {
+        player: EntityId,
+    },
+    BallPass {
+        passer: EntityId,
+        catcher: EntityId,
+        distance: f32,
+        duration: f32,
+    },
+    BallDrop {
+        player: EntityId,
+        attacker: Option<EntityId>,
+    },
+    BallSteal {
+        victim: EntityId,
+        attacker: EntityId,
+    },
+    BallBlock {
+        player: EntityId,
+        blocker: EntityId,
+    },
+    BallScore {
+        scorer: EntityId,
+        assister: Option<EntityId>,
+        points: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum RoundTimerState {
+    Setup = 0,
+    #[default]
+    Normal = 1,
+}
+
+impl RoundTimerState {
+    pub fn new(number: i64) -> Self {
+        match number {
+            0 => RoundTimerState::Setup,
+            _ => RoundTimerState::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct RoundTimer {
+    pub entity: EntityId,
+    /// The team owning the timer for koth timers, `Team::Other` for normal round timers
+    pub team: Team,
+    pub state: RoundTimerState,
+    pub paused: bool,
+    pub disabled: bool,
+    pub show_in_hud: bool,
+    /// Time remaining when the timer was paused
+    pub time_remaining: f32,
+    /// Server time at which the timer ends when not paused
+    pub end_time: f32,
+    pub length: u32,
+    pub setup_length: u32,
+}
+
+impl RoundTimer {
+    /// The remaining time on the timer at a server time
+    pub fn remaining_at(&self, server_time: f32) -> f32 {
+        if self.paused {
+            self.time_remaining
+        } else {
+            (self.end_time - server_time).max(0.0)
+        }
+    }
+}
+
+/// The state of a round timer after an update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoundTimerUpdate {
+    pub tick: DemoTick,
+    /// The server time at the tick of the update
+    pub server_time: f32,
+    pub timer: RoundTimer,
+}
+
+/// The time shown on the in-game clock
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RoundClock {
+    pub remaining: f32,
+    pub state: RoundTimerState,
+    pub paused: bool,
+}
+
+impl fmt::Display for RoundClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.remaining.ceil() as u32;
+        write!(f, "{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TeamState {
+    pub entity: EntityId,
+    pub team: Team,
+    /// Name of the team, tournament demos can have custom team names
+    pub name: String,
+    pub score: u32,
+    pub rounds_won: u32,
+    pub flag_captures: u32,
+    /// Entity ids of the players in the team
+    pub players: Vec<EntityId>,
+}
+
+impl TeamState {
+    /// The name of the team, with the short in-game name for unnamed teams
+    pub fn display_name(&self) -> &str {
+        match (self.name.as_str(), self.team) {
+            ("" | "Red", Team::Red) => "RED",
+            ("" | "Blue", Team::Blue) => "BLU",
+            (name, _) => name,
+        }
+    }
+}
+
+/// Format the scores of red and blue as `RED 3 - 2 BLU`
+pub fn score_line(teams: &[TeamState]) -> Option<String> {
+    let red = teams.iter().find(|team| team.team == Team::Red)?;
+    let blue = teams.iter().find(|team| team.team == Team::Blue)?;
+    Some(format!(
+        "{} {} - {} {}",
+        red.display_name(),
+        red.score,
+        blue.score,
+        blue.display_name()
+    ))
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct World {
     pub boundary_min: Vector,
@@ -413,13 +1256,40 @@ pub struct GameState {
     pub players: Vec<Player>,
     pub buildings: BTreeMap<EntityId, Building>,
     pub projectiles: BTreeMap<EntityId, Projectile>,
+    pub mediguns: BTreeMap<EntityId, Medigun>,
+    pub weapons: BTreeMap<EntityId, Weapon>,
+    /// Every time a player switched their active weapon
+    pub weapon_switches: Vec<WeaponSwitch>,
+    pub control_points: Vec<ControlPoint>,
+    /// Whether the map is played as a series of mini rounds, like attack/defend stages
+    pub playing_mini_rounds: bool,
+    /// The round timer shown in the hud
+    pub hud_round_timer: Option<EntityId>,
+    pub capture_log: Vec<CaptureEvent>,
+    pub payloads: BTreeMap<EntityId, PayloadCart>,
+    /// Progress of the payload carts, recorded every time the progress of a cart changes
+    pub payload_progress: Vec<PayloadProgress>,
+    pub trains: BTreeMap<EntityId, Train>,
+    pub flags: BTreeMap<EntityId, Flag>,
+    pub ball: Option<PasstimeBall>,
+    /// Timeline of flag and passtime ball events
+    pub objective_events: Vec<ObjectiveEvent>,
+    pub round_timers: BTreeMap<EntityId, RoundTimer>,
+    pub teams: Vec<TeamState>,
+    /// Every change made to the round timers
+    pub round_timer_history: Vec<RoundTimerUpdate>,
+    /// Timeline of player conditions being added and removed
+    pub condition_changes: Vec<ConditionChange>,
+    /// Number of rounds started so far
+    pub round: u32,
     pub collisions: Vec<Collision>,
     pub world: Option<World>,
     pub kills: Vec<Kill>,
     pub tick: DemoTick,
     pub server_classes: Vec<ServerClass>,
     pub interval_per_tick: f32,
-    pub outer_map: HashMap<Handle, EntityId>,
+    /// Serial numbers of the live entities, used to resolve entity handles
+    pub entity_serials: HashMap<EntityId, u16>,
 }
 
 impl GameState {
@@ -479,4 +1349,406 @@ impl GameState {
     pub fn remove_building(&mut self, entity_id: EntityId) {
         self.buildings.remove(&entity_id);
     }
+
+    pub fn get_control_point(&self, index: u8) -> Option<&ControlPoint> {
+        self.control_points.get(index as usize)
+    }
+
+    /// Get a control point, creating all points up to the index if they don't exist yet
+    pub fn get_or_create_control_point(&mut self, index: u8) -> &mut ControlPoint {
+        while self.control_points.len() <= index as usize {
+            let index = self.control_points.len() as u8;
+            self.control_points.push(ControlPoint {
+                index,
+                ..ControlPoint::default()
+            });
+        }
+
+        #[allow(clippy::indexing_slicing)]
+        &mut self.control_points[index as usize]
+    }
+
+    /// The periods during which a player had a condition, the end is `None` if the condition was
+    /// still active at the end of the demo
+    pub fn condition_periods(
+        &self,
+        player: EntityId,
+        condition: PlayerCondition,
+    ) -> Vec<(DemoTick, Option<DemoTick>)> {
+        let mut periods: Vec<(DemoTick, Option<DemoTick>)> = Vec::new();
+        for change in self
+            .condition_changes
+            .iter()
+            .filter(|change| change.player == player && change.condition == condition)
+        {
+            match (change.active, periods.last_mut()) {
+                (true, _) => periods.push((change.tick, None)),
+                (false, Some((_, end @ None))) => *end = Some(change.tick),
+                (false, _) => {}
+            }
+        }
+        periods
+    }
+
+    pub fn get_team(&self, team: Team) -> Option<&TeamState> {
+        self.teams.iter().find(|state| state.team == team)
+    }
+
+    /// The current score as `RED 3 - 2 BLU`
+    pub fn score_line(&self) -> Option<String> {
+        score_line(&self.teams)
+    }
+
+    /// The time shown on the round clock at a tick
+    pub fn round_clock(&self, tick: DemoTick) -> Option<RoundClock> {
+        self.timer_clock(tick, Team::Other)
+    }
+
+    /// The time shown on the clock of a team at a tick, for koth timers
+    ///
+    /// The clock is based on the last change before the tick to a timer that is visible and owned
+    /// by the team at that point.
+    pub fn timer_clock(&self, tick: DemoTick, team: Team) -> Option<RoundClock> {
+        let mut seen_timers = Vec::new();
+        let update = self
+            .round_timer_history
+            .iter()
+            .rev()
+            .filter(|update| update.tick <= tick)
+            // only the latest state of every timer counts
+            .filter(|update| {
+                let latest = !seen_timers.contains(&update.timer.entity);
+                seen_timers.push(update.timer.entity);
+                latest
+            })
+            .find(|update| {
+                update.timer.team == team && update.timer.show_in_hud && !update.timer.disabled
+            })?;
+        let elapsed =
+            u32::from(tick).saturating_sub(u32::from(update.tick)) as f32 * self.interval_per_tick;
+        Some(RoundClock {
+            remaining: update.timer.remaining_at(update.server_time + elapsed),
+            state: update.timer.state,
+            paused: update.timer.paused,
+        })
+    }
+
+    /// Find the train moving a payload cart
+    ///
+    /// Carts are matched with the train of the same team, maps with a single train use it for
+    /// every cart.
+    pub fn get_payload_train(&self, cart: &PayloadCart) -> Option<&Train> {
+        match self.trains.len() {
+            1 => self.trains.values().next(),
+            _ => self.trains.values().find(|train| train.team == cart.team),
+        }
+    }
+
+    /// Find the medigun held by a player through the weapon handles of the player
+    pub fn get_medigun(&self, player: &Player) -> Option<&Medigun> {
+        player
+            .weapons
+            .values()
+            .filter_map(|handle| self.resolve_handle(*handle))
+            .find_map(|entity| self.mediguns.get(&entity))
+    }
+
+    /// Get the entity a handle points to, if the entity still exists
+    pub fn resolve_handle(&self, handle: Handle) -> Option<EntityId> {
+        let entity = handle.index()?;
+        (self.entity_serials.get(&entity) == Some(&handle.serial())).then_some(entity)
+    }
+
+    /// Check if a handle points to an entity index that is now used by a different entity
+    pub fn is_stale_handle(&self, handle: Handle) -> bool {
+        handle
+            .index()
+            .and_then(|entity| self.entity_serials.get(&entity))
+            .is_some_and(|serial| *serial != handle.serial())
+    }
+
+    pub fn get_player_by_handle(&self, handle: Handle) -> Option<&Player> {
+        self.get_player(self.resolve_handle(handle)?)
+    }
+
+    pub fn get_weapon(&self, handle: Handle) -> Option<&Weapon> {
+        self.weapons.get(&self.resolve_handle(handle)?)
+    }
+
+    pub fn loadout(&self, player: &Player) -> Loadout<'_> {
+        Loadout {
+            weapons: player
+                .weapons
+                .values()
+                .filter_map(|handle| self.get_weapon(*handle))
+                .collect(),
+            active: self.get_weapon(player.active_weapon),
+        }
+    }
+
+    /// Find the player holding a weapon
+    pub fn get_weapon_owner(&self, weapon: Handle) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|player| player.weapons.values().any(|handle| *handle == weapon))
+    }
+
+    /// The weapon a player had active at a tick
+    pub fn active_weapon_at(&self, player: EntityId, tick: DemoTick) -> Option<&WeaponSwitch> {
+        self.weapon_switches
+            .iter()
+            .rev()
+            .find(|switch| switch.player == player && switch.tick <= tick)
+    }
+
+    /// The weapon the attacker had active when making a kill
+    pub fn get_kill_weapon(&self, kill: &Kill) -> Option<&WeaponSwitch> {
+        let attacker = self.players.iter().find(|player| {
+            player.info.as_ref().map(|info| info.user_id) == Some(UserId::from(kill.attacker_id))
+        })?;
+        self.active_weapon_at(attacker.entity, kill.tick)
+    }
+
+    /// Find the player holding a medigun
+    pub fn get_medigun_owner(&self, medigun: EntityId) -> Option<&Player> {
+        self.players.iter().find(|player| {
+            player
+                .weapons
+                .values()
+                .any(|handle| self.resolve_handle(*handle) == Some(medigun))
+        })
+    }
+}
+
+#[test]
+fn test_medigun_owner() {
+    let mut state = GameState::default();
+    let medic = state.get_or_create_player(EntityId::from(3u32));
+    medic.weapons = BTreeMap::from([
+        (0, Handle::new(EntityId::from(49u32), 7)),
+        (1, Handle::new(EntityId::from(50u32), 7)),
+        (2, Handle::new(EntityId::from(51u32), 7)),
+    ]);
+    state.get_or_create_player(EntityId::from(4u32));
+
+    state.entity_serials.insert(EntityId::from(50u32), 7);
+    state.mediguns.insert(
+        EntityId::from(50u32),
+        Medigun {
+            ty: MedigunType::new(411),
+            ..Medigun::new(EntityId::from(50u32))
+        },
+    );
+
+    let medic = state.get_player(EntityId::from(3u32)).unwrap();
+    let medigun = state.get_medigun(medic).unwrap();
+    assert_eq!(EntityId::from(50u32), medigun.entity);
+    assert_eq!(MedigunType::QuickFix, medigun.ty);
+    assert_eq!(
+        Some(EntityId::from(3u32)),
+        state
+            .get_medigun_owner(EntityId::from(50u32))
+            .map(|player| player.entity)
+    );
+
+    let other = state.get_player(EntityId::from(4u32)).unwrap();
+    assert_eq!(None, state.get_medigun(other));
+    assert_eq!(MedigunType::Stock, MedigunType::new(211));
+    assert_eq!(MedigunType::Vaccinator, MedigunType::new(998));
+}
+
+#[test]
+fn test_round_clock() {
+    let mut state = GameState {
+        interval_per_tick: 0.015,
+        ..GameState::default()
+    };
+    let timer = RoundTimer {
+        entity: EntityId::from(100u32),
+        show_in_hud: true,
+        end_time: 400.0,
+        length: 300,
+        ..RoundTimer::default()
+    };
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(1000u32),
+        server_time: 100.0,
+        timer: timer.clone(),
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(3000u32),
+        server_time: 130.0,
+        timer: RoundTimer {
+            paused: true,
+            time_remaining: 270.0,
+            ..timer.clone()
+        },
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(3000u32),
+        server_time: 130.0,
+        timer: RoundTimer {
+            entity: EntityId::from(101u32),
+            team: Team::Blue,
+            end_time: 310.0,
+            ..timer
+        },
+    });
+
+    assert_eq!(None, state.round_clock(DemoTick::from(999u32)));
+    let clock = state.round_clock(DemoTick::from(1000u32)).unwrap();
+    assert_eq!("5:00", clock.to_string());
+    // 15 seconds later
+    assert_eq!(
+        "4:45",
+        state
+            .round_clock(DemoTick::from(2000u32))
+            .unwrap()
+            .to_string()
+    );
+    let paused = state.round_clock(DemoTick::from(5000u32)).unwrap();
+    assert!(paused.paused);
+    assert_eq!("4:30", paused.to_string());
+
+    assert_eq!(
+        "3:00",
+        state
+            .timer_clock(DemoTick::from(3000u32), Team::Blue)
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(None, state.timer_clock(DemoTick::from(3000u32), Team::Red));
+}
+
+#[test]
+fn test_round_clock_hidden_timers() {
+    let mut state = GameState {
+        interval_per_tick: 0.015,
+        ..GameState::default()
+    };
+    let timer = RoundTimer {
+        entity: EntityId::from(100u32),
+        show_in_hud: true,
+        end_time: 400.0,
+        length: 300,
+        ..RoundTimer::default()
+    };
+    // koth timer that is re-tagged for the red team later on
+    let koth_timer = RoundTimer {
+        entity: EntityId::from(101u32),
+        end_time: 280.0,
+        ..timer.clone()
+    };
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(1000u32),
+        server_time: 100.0,
+        timer: timer.clone(),
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(1000u32),
+        server_time: 100.0,
+        timer: koth_timer.clone(),
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(2000u32),
+        server_time: 115.0,
+        timer: RoundTimer {
+            show_in_hud: false,
+            ..timer.clone()
+        },
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(3000u32),
+        server_time: 130.0,
+        timer: RoundTimer {
+            team: Team::Red,
+            ..koth_timer
+        },
+    });
+    state.round_timer_history.push(RoundTimerUpdate {
+        tick: DemoTick::from(4000u32),
+        server_time: 145.0,
+        timer: RoundTimer {
+            disabled: true,
+            ..timer
+        },
+    });
+
+    // before the timers change the clock shows the most recent visible timer
+    assert_eq!(
+        "3:00",
+        state
+            .round_clock(DemoTick::from(1000u32))
+            .unwrap()
+            .to_string()
+    );
+    // the round timer is hidden, the koth timer is still counting down
+    assert_eq!(
+        "2:45",
+        state
+            .round_clock(DemoTick::from(2000u32))
+            .unwrap()
+            .to_string()
+    );
+    // the koth timer now belongs to red and the round timer is still hidden, the older visible
+    // updates of both timers don't count anymore
+    assert_eq!(None, state.round_clock(DemoTick::from(3000u32)));
+    assert_eq!(
+        "2:30",
+        state
+            .timer_clock(DemoTick::from(3000u32), Team::Red)
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(None, state.round_clock(DemoTick::from(5000u32)));
+}
+
+#[test]
+fn test_handle() {
+    let handle = Handle::from(1606276);
+    assert_eq!(Some(EntityId::from(644u32)), handle.index());
+    assert_eq!(784, handle.serial());
+    assert_eq!(handle, Handle::new(EntityId::from(644u32), 784));
+    assert!(!Handle::default().is_valid());
+    assert_eq!(None, Handle::from(0x1FFFFF).index());
+
+    let mut state = GameState::default();
+    assert_eq!(None, state.resolve_handle(handle));
+    assert!(!state.is_stale_handle(handle));
+
+    state.entity_serials.insert(EntityId::from(644u32), 784);
+    assert_eq!(Some(EntityId::from(644u32)), state.resolve_handle(handle));
+    assert!(!state.is_stale_handle(handle));
+
+    // the entity index is re-used for a new entity
+    state.entity_serials.insert(EntityId::from(644u32), 785);
+    assert_eq!(None, state.resolve_handle(handle));
+    assert!(state.is_stale_handle(handle));
+    assert_eq!(None, state.resolve_handle(Handle::INVALID));
+}
+
+#[test]
+fn test_builder_user_id() {
+    let mut state = GameState::default();
+    let engineer = state.get_or_create_player(EntityId::from(3u32));
+    engineer.info = Some(UserInfo {
+        classes: Default::default(),
+        name: "engineer".into(),
+        user_id: UserId::from(12u16),
+        steam_id: String::new(),
+        entity_id: EntityId::from(3u32),
+        team: Team::Red,
+    });
+    state.entity_serials.insert(EntityId::from(3u32), 5);
+
+    let mut sentry = Building::new(EntityId::from(80u32), BuildingClass::Sentry);
+    assert_eq!(None, sentry.builder_user_id(&state));
+    if let Building::Sentry(sentry) = &mut sentry {
+        sentry.builder = Handle::new(EntityId::from(3u32), 5);
+    }
+    assert_eq!(Some(UserId::from(12u16)), sentry.builder_user_id(&state));
+
+    // the builder disconnected and the entity index was re-used
+    state.entity_serials.insert(EntityId::from(3u32), 6);
+    assert_eq!(None, sentry.builder_user_id(&state));
 }
@@ -1,4 +1,5 @@
 pub mod game_state;
+pub mod stringtables;
 pub mod userinfo;
 
 use bitbuffer::{BitRead, BitReadStream, BitWrite, BitWriteStream, Endianness};
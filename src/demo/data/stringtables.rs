@@ -0,0 +1,213 @@
+//! Contents of the string tables sent during the demo
+//!
+//! Only the text of the entries is stored, the extra data of the entries is only available while
+//! handling the entry. The parser state only keeps the precache tables, analysers that need other
+//! tables can collect them from `handle_string_entry`.
+
+use crate::demo::data::DemoTick;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const MODEL_PRECACHE: &str = "modelprecache";
+pub const SOUND_PRECACHE: &str = "soundprecache";
+pub const DECAL_PRECACHE: &str = "decalprecache";
+pub const GENERIC_PRECACHE: &str = "genericprecache";
+pub const PARTICLE_EFFECT_NAMES: &str = "ParticleEffectNames";
+
+/// Tables that are kept in the parser state, the history of changes is kept for these tables
+pub const PRECACHE_TABLES: [&str; 5] = [
+    MODEL_PRECACHE,
+    SOUND_PRECACHE,
+    DECAL_PRECACHE,
+    GENERIC_PRECACHE,
+    PARTICLE_EFFECT_NAMES,
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<StringTableData>", into = "Vec<StringTableData>")]
+pub struct StringTables {
+    tables: Vec<StringTableData>,
+    // position of the tables by name
+    positions: HashMap<String, usize>,
+}
+
+impl From<Vec<StringTableData>> for StringTables {
+    fn from(tables: Vec<StringTableData>) -> Self {
+        let positions = tables
+            .iter()
+            .enumerate()
+            .map(|(position, data)| (data.name.clone(), position))
+            .collect();
+        StringTables { tables, positions }
+    }
+}
+
+impl From<StringTables> for Vec<StringTableData> {
+    fn from(tables: StringTables) -> Self {
+        tables.tables
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StringTableData {
+    pub name: String,
+    /// The text of the entries, indexed by entry index
+    pub entries: Vec<Option<String>>,
+    /// Every update made to the table, in the order they were made
+    ///
+    /// Only kept for the precache tables
+    pub history: Vec<StringTableChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringTableChange {
+    pub tick: DemoTick,
+    pub index: usize,
+    pub text: Option<String>,
+}
+
+impl StringTableData {
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index)?.as_deref()
+    }
+
+    /// Find the index of an entry by its text
+    pub fn index_of(&self, text: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.as_deref() == Some(text))
+    }
+}
+
+impl StringTables {
+    pub fn get(&self, table: &str) -> Option<&StringTableData> {
+        self.tables.get(*self.positions.get(table)?)
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &StringTableData> {
+        self.tables.iter()
+    }
+
+    /// Get the text of an entry
+    pub fn get_string(&self, table: &str, index: usize) -> Option<&str> {
+        self.get(table)?.get(index)
+    }
+
+    /// All changes made to a precache table, with the tick of the change
+    pub fn history(&self, table: &str) -> &[StringTableChange] {
+        self.get(table)
+            .map(|data| data.history.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn model_name(&self, index: usize) -> Option<&str> {
+        self.get_string(MODEL_PRECACHE, index)
+    }
+
+    pub fn sound_name(&self, index: usize) -> Option<&str> {
+        self.get_string(SOUND_PRECACHE, index)
+    }
+
+    pub fn decal_name(&self, index: usize) -> Option<&str> {
+        self.get_string(DECAL_PRECACHE, index)
+    }
+
+    pub fn particle_name(&self, index: usize) -> Option<&str> {
+        self.get_string(PARTICLE_EFFECT_NAMES, index)
+    }
+
+    /// Set the text of an entry
+    pub fn insert(&mut self, table: &str, index: usize, text: Option<&str>, tick: DemoTick) {
+        let position = match self.positions.get(table) {
+            Some(position) => *position,
+            None => {
+                self.tables.push(StringTableData {
+                    name: table.to_string(),
+                    ..StringTableData::default()
+                });
+                self.positions
+                    .insert(table.to_string(), self.tables.len() - 1);
+                self.tables.len() - 1
+            }
+        };
+        let Some(data) = self.tables.get_mut(position) else {
+            return;
+        };
+
+        if PRECACHE_TABLES.contains(&table) {
+            data.history.push(StringTableChange {
+                tick,
+                index,
+                text: text.map(String::from),
+            });
+        }
+        if data.entries.len() <= index {
+            data.entries.resize(index + 1, None);
+        }
+        // updates without text only change the extra data of the entry
+        if let (Some(entry), Some(text)) = (data.entries.get_mut(index), text) {
+            if entry.as_deref() != Some(text) {
+                *entry = Some(text.to_string());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_string_tables() {
+    let mut tables = StringTables::default();
+    tables.insert(
+        MODEL_PRECACHE,
+        1,
+        Some("maps/pl_upward.bsp"),
+        DemoTick::from(0u32),
+    );
+    tables.insert(
+        MODEL_PRECACHE,
+        3,
+        Some("models/weapons/w_models/w_rocket.mdl"),
+        DemoTick::from(0u32),
+    );
+    tables.insert(
+        SOUND_PRECACHE,
+        0,
+        Some("Weapon_RPG.Single"),
+        DemoTick::from(0u32),
+    );
+    tables.insert(MODEL_PRECACHE, 1, None, DemoTick::from(20u32));
+    tables.insert(
+        PARTICLE_EFFECT_NAMES,
+        2,
+        Some("ExplosionCore_MidAir"),
+        DemoTick::from(40u32),
+    );
+    tables.insert("userinfo", 0, Some("2"), DemoTick::from(40u32));
+
+    assert_eq!(Some("maps/pl_upward.bsp"), tables.model_name(1));
+    assert_eq!(None, tables.model_name(2));
+    assert_eq!(
+        Some("models/weapons/w_models/w_rocket.mdl"),
+        tables.model_name(3)
+    );
+    assert_eq!(None, tables.model_name(4));
+    assert_eq!(Some("Weapon_RPG.Single"), tables.sound_name(0));
+    assert_eq!(None, tables.decal_name(0));
+    assert_eq!(Some("ExplosionCore_MidAir"), tables.particle_name(2));
+    assert_eq!(
+        Some(3),
+        tables
+            .get(MODEL_PRECACHE)
+            .unwrap()
+            .index_of("models/weapons/w_models/w_rocket.mdl")
+    );
+
+    let history = tables.history(MODEL_PRECACHE);
+    assert_eq!(3, history.len());
+    assert_eq!(DemoTick::from(20u32), history[2].tick);
+    assert_eq!(None, history[2].text);
+    assert!(tables.history(DECAL_PRECACHE).is_empty());
+    assert_eq!(1, tables.history(PARTICLE_EFFECT_NAMES).len());
+    // only the precache tables keep their history
+    assert!(tables.history("userinfo").is_empty());
+    assert_eq!(4, tables.tables().count());
+}
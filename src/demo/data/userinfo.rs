@@ -1,8 +1,9 @@
 use crate::demo::message::packetentities::EntityId;
 use crate::demo::packet::stringtable::{ExtraData, StringTableEntry};
-use crate::demo::parser::analyser::UserId;
+use crate::demo::parser::analyser::{self, UserId};
 use crate::{ReadResult, Stream};
 use bitbuffer::{BitRead, BitReadBuffer, BitReadStream, BitWrite, BitWriteStream, LittleEndian};
+use std::collections::BTreeMap;
 
 #[derive(BitRead, Debug)]
 struct RawPlayerInfo {
@@ -91,6 +92,37 @@ impl UserInfo {
         }
     }
 
+    /// Parse an entry of the `userinfo` string table, entries from other tables are ignored
+    ///
+    /// Returns the user id of the player with the parsed info, entries that can't be parsed or
+    /// don't belong to a player are skipped.
+    pub fn from_string_entry(
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+    ) -> Option<(UserId, Self)> {
+        if table != "userinfo" {
+            return None;
+        }
+        let user_info = Self::parse_from_string_table(
+            index as u16,
+            entry.text.as_deref(),
+            entry.extra_data.as_ref().map(|data| data.data.clone()),
+        )
+        .ok()??;
+        Some((user_info.player_info.user_id, user_info))
+    }
+
+    /// Add the player to the users, or update the entity id of an already known user
+    pub fn update_users(self, users: &mut BTreeMap<UserId, analyser::UserInfo>) {
+        users
+            .entry(self.player_info.user_id)
+            .and_modify(|info| {
+                info.entity_id = self.entity_id;
+            })
+            .or_insert_with(|| self.into());
+    }
+
     pub fn encode_to_string_table(&self) -> ReadResult<StringTableEntry<'static>> {
         let text = format!("{}", self.entity_id);
         let mut extra_data = Vec::with_capacity(132);
@@ -0,0 +1,201 @@
+//! Binary encoded KeyValues
+//!
+//! KeyValues are the nested key/value structure used by the engine for things like configuration
+//! and client commands. In binary form every key is written as a type byte followed by the
+//! null-terminated name and the value, a list of keys is closed with an end marker.
+
+use bitbuffer::{BitError, BitReadStream, BitWrite, BitWriteStream, Endianness};
+use serde::{Deserialize, Serialize};
+
+use crate::ReadResult;
+
+const TYPE_NONE: u8 = 0;
+const TYPE_STRING: u8 = 1;
+const TYPE_INT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_PTR: u8 = 4;
+const TYPE_WSTRING: u8 = 5;
+const TYPE_COLOR: u8 = 6;
+const TYPE_UINT64: u8 = 7;
+/// Marks the end of a list of keys
+const TYPE_END: u8 = 8;
+
+/// A named key with either a value or a list of sub keys
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyValues {
+    pub name: String,
+    pub value: KeyValue,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum KeyValue {
+    Section(Vec<KeyValues>),
+    String(String),
+    Int(i32),
+    Float(f32),
+    Pointer(u32),
+    /// Wide strings aren't written in binary form, only the key is stored
+    WideString,
+    Color([u8; 4]),
+    UInt64(u64),
+}
+
+impl KeyValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            KeyValue::String(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Get the value as integer, string values are parsed
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            KeyValue::Int(value) => Some(*value),
+            KeyValue::Float(value) => Some(*value as i32),
+            KeyValue::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the value as float, string values are parsed
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            KeyValue::Int(value) => Some(*value as f32),
+            KeyValue::Float(value) => Some(*value),
+            KeyValue::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl KeyValues {
+    pub fn new(name: impl Into<String>, value: KeyValue) -> Self {
+        KeyValues {
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// The sub keys of this key, empty if the key has a value
+    pub fn children(&self) -> &[KeyValues] {
+        match &self.value {
+            KeyValue::Section(children) => children,
+            _ => &[],
+        }
+    }
+
+    /// Find a direct sub key by name, names are compared case-insensitively
+    pub fn get(&self, name: &str) -> Option<&KeyValues> {
+        self.children()
+            .iter()
+            .find(|child| child.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find a nested sub key by a `/` separated path, e.g. `"Upgrade/count"`
+    pub fn find(&self, path: &str) -> Option<&KeyValues> {
+        path.split('/')
+            .try_fold(self, |key_values, name| key_values.get(name))
+    }
+
+    pub fn get_str(&self, path: &str) -> Option<&str> {
+        self.find(path)?.value.as_str()
+    }
+
+    pub fn get_int(&self, path: &str) -> Option<i32> {
+        self.find(path)?.value.as_int()
+    }
+
+    pub fn get_float(&self, path: &str) -> Option<f32> {
+        self.find(path)?.value.as_float()
+    }
+}
+
+/// Read a list of keys up to and including the end marker
+pub fn read_key_values<E: Endianness>(
+    stream: &mut BitReadStream<'_, E>,
+) -> ReadResult<Vec<KeyValues>> {
+    let mut keys = Vec::new();
+    loop {
+        let ty: u8 = stream.read()?;
+        if ty == TYPE_END {
+            return Ok(keys);
+        }
+        let name = stream.read()?;
+        let value = match ty {
+            TYPE_NONE => KeyValue::Section(read_key_values(stream)?),
+            TYPE_STRING => KeyValue::String(stream.read()?),
+            TYPE_INT => KeyValue::Int(stream.read()?),
+            TYPE_FLOAT => KeyValue::Float(stream.read()?),
+            TYPE_PTR => KeyValue::Pointer(stream.read()?),
+            TYPE_WSTRING => KeyValue::WideString,
+            TYPE_COLOR => KeyValue::Color(stream.read()?),
+            TYPE_UINT64 => KeyValue::UInt64(stream.read()?),
+            _ => {
+                return Err(BitError::UnmatchedDiscriminant {
+                    discriminant: ty as usize,
+                    enum_name: "KeyValue".to_string(),
+                })
+            }
+        };
+        keys.push(KeyValues { name, value });
+    }
+}
+
+/// Write a list of keys followed by the end marker
+pub fn write_key_values<E: Endianness>(
+    keys: &[KeyValues],
+    stream: &mut BitWriteStream<E>,
+) -> ReadResult<()> {
+    for key in keys {
+        let ty = match &key.value {
+            KeyValue::Section(_) => TYPE_NONE,
+            KeyValue::String(_) => TYPE_STRING,
+            KeyValue::Int(_) => TYPE_INT,
+            KeyValue::Float(_) => TYPE_FLOAT,
+            KeyValue::Pointer(_) => TYPE_PTR,
+            KeyValue::WideString => TYPE_WSTRING,
+            KeyValue::Color(_) => TYPE_COLOR,
+            KeyValue::UInt64(_) => TYPE_UINT64,
+        };
+        ty.write(stream)?;
+        key.name.write(stream)?;
+        match &key.value {
+            KeyValue::Section(children) => write_key_values(children, stream)?,
+            KeyValue::String(value) => value.write(stream)?,
+            KeyValue::Int(value) => value.write(stream)?,
+            KeyValue::Float(value) => value.write(stream)?,
+            KeyValue::Pointer(value) => value.write(stream)?,
+            KeyValue::WideString => {}
+            KeyValue::Color(value) => value.write(stream)?,
+            KeyValue::UInt64(value) => value.write(stream)?,
+        }
+    }
+    TYPE_END.write(stream)
+}
+
+#[test]
+fn test_key_values_lookup() {
+    let upgrade = KeyValues::new(
+        "MVM_Upgrade",
+        KeyValue::Section(vec![KeyValues::new(
+            "Upgrade",
+            KeyValue::Section(vec![
+                KeyValues::new("itemslot", KeyValue::Int(-1)),
+                KeyValues::new("Upgrade", KeyValue::Int(19)),
+                KeyValues::new("count", KeyValue::String("1".into())),
+            ]),
+        )]),
+    );
+
+    assert_eq!(Some(-1), upgrade.get_int("upgrade/itemslot"));
+    assert_eq!(Some(19), upgrade.get_int("Upgrade/Upgrade"));
+    assert_eq!(Some(1), upgrade.get_int("Upgrade/count"));
+    assert_eq!(Some(1.0), upgrade.get_float("Upgrade/count"));
+    assert_eq!(Some("1"), upgrade.get_str("Upgrade/count"));
+    assert_eq!(None, upgrade.get_str("Upgrade/itemslot"));
+    assert_eq!(None, upgrade.find("Upgrade/missing"));
+    assert_eq!(1, upgrade.children().len());
+}
@@ -0,0 +1,599 @@
+//! Concatenate multiple demos of the same server session into a single demo
+//!
+//! When a recording is split into multiple files, because the client reconnected, because
+//! recording was restarted, or because the server changed level, the separate demos can be merged
+//! back into a single demo.
+//!
+//! The sign-on data of the first demo is used for the merged demo, the sign-on data of the
+//! following demos on the same map is checked against it and any string table changes are
+//! converted into updates. Game events that are only defined in the following demos are added to
+//! the game event list of the merged demo.
+//!
+//! When a following demo is recorded on a different map, its full sign-on sequence is kept at the
+//! boundary, starting with the new server info, so parsers can reset their per-map state there.
+//! The new server info still has to come from the same server version, game and tick rate.
+
+use crate::demo::data::DemoTick;
+use crate::demo::gamevent::{GameEventDefinition, GameEventType};
+use crate::demo::header::Header;
+use crate::demo::message::gameevent::{GameEventListMessage, GameEventTypeId};
+use crate::demo::message::stringtable::UpdateStringTableMessage;
+use crate::demo::message::{Message, ServerInfoMessage};
+use crate::demo::packet::datatable::DataTablePacket;
+use crate::demo::packet::message::MessagePacket;
+use crate::demo::packet::stop::StopPacket;
+use crate::demo::packet::stringtable::{StringTable, StringTableEntry};
+use crate::demo::packet::Packet;
+use crate::demo::parser::{DemoHandler, Encode, NullHandler, RawPacketStream};
+use crate::{Demo, ParseError};
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while merging demos
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("No demos provided to merge")]
+    NoDemos,
+    #[error("Error while processing demo {index}: {source}")]
+    Parse {
+        index: usize,
+        #[source]
+        source: ParseError,
+    },
+    #[error("Demo {index} can't be merged: {reason}")]
+    Incompatible {
+        index: usize,
+        reason: Incompatibility,
+    },
+}
+
+/// The reason why a demo can't be appended to the previous demos
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq)]
+pub enum Incompatibility {
+    #[error("network protocol {found} doesn't match {expected}")]
+    Protocol { expected: u32, found: u32 },
+    #[error("map {found} doesn't match {expected}")]
+    Map { expected: String, found: String },
+    #[error("server info field {0} doesn't match")]
+    ServerInfo(&'static str),
+    #[error("server info is missing")]
+    MissingServerInfo,
+    #[error("data tables don't match")]
+    DataTables,
+    #[error("game event {0} isn't defined")]
+    UnknownGameEvent(String),
+    #[error("game event {0} has different fields than in the previous demos")]
+    GameEventMismatch(String),
+    #[error("too many distinct game events to merge")]
+    TooManyGameEvents,
+    #[error("string table {0} isn't created in the first demo")]
+    UnknownStringTable(String),
+    #[error("string table {0} can't hold entry {1}")]
+    StringTableFull(String, u16),
+}
+
+/// Merge the demos into a single demo
+///
+/// All demos have to be recorded on the same server with the same network protocol. Consecutive
+/// demos on the same map need identical data tables, a demo on a new map starts a new sign-on
+/// sequence in the merged demo. The header of the merged demo keeps the map of the first demo.
+///
+/// The ticks of each demo are rebased to continue after the last tick of the previous demo.
+pub fn merge_demos(demos: &[Demo]) -> Result<Vec<u8>, MergeError> {
+    let (first, _) = demos.split_first().ok_or(MergeError::NoDemos)?;
+
+    let mut merger = DemoMerger::default();
+    let mut body = Vec::with_capacity(first.get_stream().bit_len() / 8 * demos.len());
+    {
+        let mut out = BitWriteStream::new(&mut body, LittleEndian);
+        for (index, demo) in demos.iter().enumerate() {
+            merger
+                .append(index, demo, &mut out)
+                .map_err(|error| error.for_demo(index))?;
+        }
+
+        Packet::Stop(StopPacket {
+            tick: merger.last_tick,
+        })
+        .encode(&mut out, &merger.encode_handler.state_handler)
+        .map_err(|source| MergeError::Parse {
+            index: demos.len() - 1,
+            source,
+        })?;
+    }
+
+    let header = merger.header().ok_or(MergeError::NoDemos)?;
+    let mut out = Vec::with_capacity(body.len() + 1072);
+    {
+        let mut stream = BitWriteStream::new(&mut out, LittleEndian);
+        header
+            .write(&mut stream)
+            .map_err(|source| MergeError::Parse {
+                index: 0,
+                source: source.into(),
+            })?;
+    }
+    out.extend_from_slice(&body);
+
+    Ok(out)
+}
+
+enum AppendError {
+    Parse(ParseError),
+    Incompatible(Incompatibility),
+}
+
+impl AppendError {
+    fn for_demo(self, index: usize) -> MergeError {
+        match self {
+            AppendError::Parse(source) => MergeError::Parse { index, source },
+            AppendError::Incompatible(reason) => MergeError::Incompatible { index, reason },
+        }
+    }
+}
+
+impl From<ParseError> for AppendError {
+    fn from(err: ParseError) -> Self {
+        AppendError::Parse(err)
+    }
+}
+
+impl From<bitbuffer::BitError> for AppendError {
+    fn from(err: bitbuffer::BitError) -> Self {
+        AppendError::Parse(err.into())
+    }
+}
+
+impl From<Incompatibility> for AppendError {
+    fn from(err: Incompatibility) -> Self {
+        AppendError::Incompatible(err)
+    }
+}
+
+#[derive(Default)]
+struct DemoMerger<'a> {
+    encode_handler: DemoHandler<'a, NullHandler>,
+    header: Option<Header>,
+    /// The map of the last appended demo
+    map: String,
+    frames: u32,
+    server_info: Option<ServerInfoMessage>,
+    data_tables: Option<DataTablePacket>,
+    /// The content of the string tables in the merged demo, by table name
+    tables: HashMap<String, Vec<StringTableEntry<'static>>>,
+    last_tick: DemoTick,
+}
+
+/// Per-demo state needed to translate the packets of a demo into the merged demo
+#[derive(Default)]
+struct DemoMapping {
+    tick_offset: u32,
+    event_ids: HashMap<GameEventTypeId, GameEventType>,
+}
+
+impl<'a> DemoMerger<'a> {
+    fn append(
+        &mut self,
+        index: usize,
+        demo: &Demo<'a>,
+        out: &mut BitWriteStream<LittleEndian>,
+    ) -> Result<(), AppendError> {
+        let mut stream = demo.get_stream();
+        let header = Header::read(&mut stream)?;
+        self.check_header(&header)?;
+        self.frames = self.frames.saturating_add(header.frames);
+
+        let map_change = index > 0 && self.map != header.map;
+        if map_change {
+            // the tables of the new map are created from scratch by its sign-on sequence
+            self.tables.clear();
+        }
+        self.map.clone_from(&header.map);
+
+        let mut mapping = DemoMapping {
+            tick_offset: if index == 0 {
+                0
+            } else {
+                u32::from(self.last_tick) + 1
+            },
+            ..DemoMapping::default()
+        };
+
+        let mut handler = DemoHandler::parse_all_with_analyser(NullHandler);
+        let mut packets = RawPacketStream::new(stream);
+
+        while let Some(packet) = packets.next(&handler.state_handler)? {
+            let translated = if index == 0 || map_change {
+                self.translate_new_map(packet.clone(), index == 0)?
+            } else {
+                self.translate(packet.clone(), &mut mapping, &handler)?
+            };
+            handler.handle_packet(packet)?;
+
+            if let Some(mut translated) = translated {
+                translated.set_tick(translated.tick() + mapping.tick_offset);
+                self.last_tick = self.last_tick.max(translated.tick());
+                translated.encode(out, &self.encode_handler.state_handler)?;
+                self.track_string_tables(&translated);
+                self.encode_handler.handle_packet(translated)?;
+            }
+        }
+
+        if self.header.is_none() {
+            self.header = Some(header);
+        }
+
+        Ok(())
+    }
+
+    fn check_header(&self, header: &Header) -> Result<(), Incompatibility> {
+        if let Some(first) = &self.header {
+            if first.protocol != header.protocol {
+                return Err(Incompatibility::Protocol {
+                    expected: first.protocol,
+                    found: header.protocol,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Packets from the first demo, or from a demo that starts a new map, are copied as-is,
+    /// except for the stop packet
+    ///
+    /// The sign-on packets of a new map become regular packets, the server info they start with
+    /// marks the new sign-on sequence and is checked against the server info of the first demo.
+    fn translate_new_map(
+        &mut self,
+        packet: Packet<'a>,
+        first: bool,
+    ) -> Result<Option<Packet<'a>>, Incompatibility> {
+        Ok(match packet {
+            Packet::Stop(_) => None,
+            Packet::SyncTick(_) if !first => None,
+            Packet::DataTables(tables) => {
+                self.data_tables = Some(tables.clone());
+                Some(Packet::DataTables(tables))
+            }
+            Packet::Signon(message_packet) => {
+                for message in &message_packet.messages {
+                    if let Message::ServerInfo(info) = message {
+                        if !first {
+                            self.check_server(info)?;
+                        }
+                        self.server_info = Some(info.as_ref().clone());
+                    }
+                }
+                Some(if first {
+                    Packet::Signon(message_packet)
+                } else {
+                    Packet::Message(message_packet)
+                })
+            }
+            packet => Some(packet),
+        })
+    }
+
+    fn translate(
+        &mut self,
+        packet: Packet<'a>,
+        mapping: &mut DemoMapping,
+        handler: &DemoHandler<'a, NullHandler>,
+    ) -> Result<Option<Packet<'a>>, AppendError> {
+        match packet {
+            Packet::Stop(_) | Packet::SyncTick(_) | Packet::StringTables(_) => Ok(None),
+            Packet::DataTables(tables) => {
+                match &self.data_tables {
+                    Some(first)
+                        if first.tables == tables.tables
+                            && first.server_classes == tables.server_classes => {}
+                    _ => return Err(Incompatibility::DataTables.into()),
+                }
+                Ok(None)
+            }
+            Packet::Signon(message_packet) | Packet::Message(message_packet) => {
+                let mut messages = Vec::with_capacity(message_packet.messages.len());
+                for message in message_packet.messages {
+                    match message {
+                        Message::ServerInfo(info) => self.check_server_info(&info)?,
+                        Message::GameEventList(list) => {
+                            mapping.event_ids = list
+                                .event_list
+                                .iter()
+                                .map(|definition| (definition.id, definition.event_type.clone()))
+                                .collect();
+                            let state = &mut self.encode_handler.state_handler;
+                            if let Some(event_list) =
+                                merge_event_lists(&state.event_definitions, list.event_list)?
+                            {
+                                // events later in this packet need the new definitions to be encoded
+                                state.event_definitions = event_list.clone();
+                                messages.push(Message::GameEventList(GameEventListMessage {
+                                    event_list,
+                                }));
+                            }
+                        }
+                        Message::SignOnState(_) | Message::ClassInfo(_) => {}
+                        Message::CreateStringTable(message) => {
+                            if let Some(update) = self.table_diff(message.table)? {
+                                messages.push(Message::UpdateStringTable(update));
+                            }
+                        }
+                        Message::UpdateStringTable(mut message) => {
+                            let name = handler
+                                .string_table_names
+                                .get(message.table_id as usize)
+                                .ok_or(ParseError::StringTableNotFound(message.table_id))?;
+                            message.table_id = self.table_id(name)?;
+                            messages.push(Message::UpdateStringTable(message));
+                        }
+                        Message::GameEvent(mut message) => {
+                            let event_type = mapping
+                                .event_ids
+                                .get(&message.event_type_id)
+                                .unwrap_or(&message.event_type);
+                            message.event_type_id = self.event_id(event_type)?;
+                            messages.push(Message::GameEvent(message));
+                        }
+                        message => messages.push(message),
+                    }
+                }
+
+                if messages.is_empty() {
+                    Ok(None)
+                } else {
+                    // sign-on packets of appended demos become regular packets in the merged demo
+                    Ok(Some(Packet::Message(MessagePacket {
+                        messages,
+                        ..message_packet
+                    })))
+                }
+            }
+            packet => Ok(Some(packet)),
+        }
+    }
+
+    /// Check the server info fields that have to match for every appended demo, on any map
+    fn check_server(&self, info: &ServerInfoMessage) -> Result<(), Incompatibility> {
+        let first = self
+            .server_info
+            .as_ref()
+            .ok_or(Incompatibility::MissingServerInfo)?;
+        if first.version != info.version {
+            return Err(Incompatibility::ServerInfo("version"));
+        }
+        if first.game != info.game {
+            return Err(Incompatibility::ServerInfo("game"));
+        }
+        if first.max_classes != info.max_classes {
+            return Err(Incompatibility::ServerInfo("max_classes"));
+        }
+        if first.interval_per_tick != info.interval_per_tick {
+            return Err(Incompatibility::ServerInfo("interval_per_tick"));
+        }
+        Ok(())
+    }
+
+    /// Check the server info of a demo that continues on the same map
+    fn check_server_info(&self, info: &ServerInfoMessage) -> Result<(), Incompatibility> {
+        self.check_server(info)?;
+        let first = self
+            .server_info
+            .as_ref()
+            .ok_or(Incompatibility::MissingServerInfo)?;
+        if first.map != info.map {
+            return Err(Incompatibility::Map {
+                expected: first.map.clone(),
+                found: info.map.clone(),
+            });
+        }
+        if first.map_hash != info.map_hash {
+            return Err(Incompatibility::ServerInfo("map_hash"));
+        }
+        Ok(())
+    }
+
+    fn table_id(&self, name: &str) -> Result<u8, Incompatibility> {
+        self.encode_handler
+            .string_table_names
+            .iter()
+            .position(|table_name| table_name == name)
+            .map(|id| id as u8)
+            .ok_or_else(|| Incompatibility::UnknownStringTable(name.into()))
+    }
+
+    fn event_id(&self, event_type: &GameEventType) -> Result<GameEventTypeId, Incompatibility> {
+        self.encode_handler
+            .state_handler
+            .event_definitions
+            .iter()
+            .find(|definition| definition.event_type == *event_type)
+            .map(|definition| definition.id)
+            .ok_or_else(|| Incompatibility::UnknownGameEvent(event_type.as_str().into()))
+    }
+
+    /// Create an update for all entries that differ from the table in the merged demo
+    fn table_diff(
+        &self,
+        table: StringTable<'a>,
+    ) -> Result<Option<UpdateStringTableMessage<'a>>, Incompatibility> {
+        let table_id = self.table_id(&table.name)?;
+        let existing = self.tables.get(table.name.as_ref());
+        let max_entries = self
+            .encode_handler
+            .state_handler
+            .string_tables
+            .get(table_id as usize)
+            .map(|meta| meta.max_entries)
+            .unwrap_or_default();
+
+        let mut entries = Vec::new();
+        for (index, entry) in table.entries {
+            if index >= max_entries {
+                return Err(Incompatibility::StringTableFull(table.name.into(), index));
+            }
+            let current = existing.and_then(|entries| entries.get(index as usize));
+            let changed = match current {
+                Some(current) => {
+                    current.text() != entry.text() || current.extra_data != entry.extra_data
+                }
+                None => true,
+            };
+            if changed {
+                entries.push((index, entry));
+            }
+        }
+
+        Ok(if entries.is_empty() {
+            None
+        } else {
+            Some(UpdateStringTableMessage { entries, table_id })
+        })
+    }
+
+    fn track_string_tables(&mut self, packet: &Packet) {
+        if let Packet::Signon(message_packet) | Packet::Message(message_packet) = packet {
+            for message in &message_packet.messages {
+                match message {
+                    Message::CreateStringTable(message) => {
+                        let entries = self
+                            .tables
+                            .entry(message.table.name.to_string())
+                            .or_default();
+                        for (index, entry) in &message.table.entries {
+                            set_table_entry(entries, *index, entry);
+                        }
+                    }
+                    Message::UpdateStringTable(message) => {
+                        if let Some(name) = self
+                            .encode_handler
+                            .string_table_names
+                            .get(message.table_id as usize)
+                        {
+                            let entries = self.tables.entry(name.to_string()).or_default();
+                            for (index, entry) in &message.entries {
+                                set_table_entry(entries, *index, entry);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn header(&self) -> Option<Header> {
+        let mut header = self.header.clone()?;
+        let interval_per_tick = self
+            .server_info
+            .as_ref()
+            .map(|info| info.interval_per_tick)
+            .unwrap_or(0.015);
+        header.ticks = self.last_tick.into();
+        header.frames = self.frames;
+        header.duration = header.ticks as f32 * interval_per_tick;
+        Some(header)
+    }
+}
+
+/// Add the game events that aren't defined yet to the event list
+///
+/// Returns `None` if all events are already defined
+fn merge_event_lists(
+    existing: &[GameEventDefinition],
+    list: Vec<GameEventDefinition>,
+) -> Result<Option<Vec<GameEventDefinition>>, Incompatibility> {
+    // event ids are encoded in 9 bits
+    const MAX_EVENTS: usize = 1 << 9;
+
+    let mut merged = existing.to_vec();
+    for definition in list {
+        match merged
+            .iter()
+            .find(|merged| merged.event_type == definition.event_type)
+        {
+            Some(merged) if merged.entries != definition.entries => {
+                return Err(Incompatibility::GameEventMismatch(
+                    definition.event_type.as_str().into(),
+                ));
+            }
+            Some(_) => {}
+            None if merged.len() >= MAX_EVENTS => return Err(Incompatibility::TooManyGameEvents),
+            None => merged.push(GameEventDefinition {
+                // the parser looks up definitions by id, so the ids have to match the position
+                id: GameEventTypeId::from(merged.len() as u16),
+                ..definition
+            }),
+        }
+    }
+
+    Ok((merged.len() > existing.len()).then_some(merged))
+}
+
+fn set_table_entry(
+    entries: &mut Vec<StringTableEntry<'static>>,
+    index: u16,
+    entry: &StringTableEntry,
+) {
+    let index = index as usize;
+    if entries.len() <= index {
+        entries.resize(index + 1, StringTableEntry::default());
+    }
+    if let Some(existing) = entries.get_mut(index) {
+        // updates without text keep the existing text
+        if entry.text.is_some() {
+            existing.text = entry.to_owned().text;
+        }
+        existing.extra_data = entry.extra_data.as_ref().map(|data| data.to_owned());
+    }
+}
+
+#[test]
+fn test_merge_event_lists() {
+    use crate::demo::gamevent::{GameEventEntry, GameEventValueType};
+
+    let definition = |id: u16, event_type: GameEventType, field: &str| GameEventDefinition {
+        id: GameEventTypeId::from(id),
+        event_type,
+        entries: vec![GameEventEntry {
+            name: field.into(),
+            kind: GameEventValueType::Short,
+        }],
+    };
+    let existing = vec![
+        definition(0, GameEventType::ServerSpawn, "a"),
+        definition(1, GameEventType::PlayerDeath, "userid"),
+    ];
+
+    // same events with different ids
+    let same = vec![
+        definition(4, GameEventType::PlayerDeath, "userid"),
+        definition(2, GameEventType::ServerSpawn, "a"),
+    ];
+    assert_eq!(Ok(None), merge_event_lists(&existing, same));
+
+    let merged = merge_event_lists(
+        &existing,
+        vec![
+            definition(0, GameEventType::PlayerHurt, "userid"),
+            definition(1, GameEventType::PlayerDeath, "userid"),
+        ],
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(3, merged.len());
+    assert_eq!(GameEventType::PlayerHurt, merged[2].event_type);
+    assert_eq!(GameEventTypeId::from(2), merged[2].id);
+    assert_eq!(GameEventType::PlayerDeath, merged[1].event_type);
+
+    assert_eq!(
+        Err(Incompatibility::GameEventMismatch("player_death".into())),
+        merge_event_lists(
+            &existing,
+            vec![definition(0, GameEventType::PlayerDeath, "victim")]
+        )
+    );
+}
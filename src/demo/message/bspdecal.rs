@@ -1,4 +1,4 @@
-use crate::demo::sendprop::{read_bit_coord, write_bit_coord};
+use crate::demo::sendprop::{read_bit_vec3_coord, write_bit_vec3_coord};
 use crate::demo::vector::Vector;
 use crate::{ReadResult, Stream};
 use bitbuffer::{BitRead, BitWrite, BitWriteSized, BitWriteStream, LittleEndian};
@@ -16,15 +16,7 @@ pub struct BSPDecalMessage {
 
 impl BitRead<'_, LittleEndian> for BSPDecalMessage {
     fn read(stream: &mut Stream) -> ReadResult<Self> {
-        let position = {
-            let (has_x, has_y, has_z) = stream.read()?;
-
-            Vector {
-                x: if has_x { read_bit_coord(stream)? } else { 0f32 },
-                y: if has_y { read_bit_coord(stream)? } else { 0f32 },
-                z: if has_z { read_bit_coord(stream)? } else { 0f32 },
-            }
-        };
+        let position = read_bit_vec3_coord(stream)?;
 
         let texture_index = stream.read_sized(9)?;
         let (ent_index, model_index): (u16, u16) = if stream.read()? {
@@ -46,20 +38,7 @@ impl BitRead<'_, LittleEndian> for BSPDecalMessage {
 
 impl BitWrite<LittleEndian> for BSPDecalMessage {
     fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
-        let has_x = self.position.x != 0.0;
-        let has_y = self.position.y != 0.0;
-        let has_z = self.position.z != 0.0;
-        (has_x, has_y, has_z).write(stream)?;
-
-        if has_x {
-            write_bit_coord(self.position.x, stream)?;
-        }
-        if has_y {
-            write_bit_coord(self.position.y, stream)?;
-        }
-        if has_z {
-            write_bit_coord(self.position.z, stream)?;
-        }
+        write_bit_vec3_coord(self.position, stream)?;
         self.texture_index.write_sized(stream, 9)?;
         if self.ent_index != 0 || self.model_index != 0 {
             true.write(stream)?;
@@ -0,0 +1,171 @@
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+use serde::{Deserialize, Serialize};
+
+use crate::demo::keyvalues::{read_key_values, write_key_values, KeyValues};
+use crate::{ReadResult, Stream};
+
+/// Client command sent as KeyValues, used for things like MvM upgrades or `use_action_slot_item`
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "'a: 'static"))]
+pub struct CmdKeyValuesMessage<'a> {
+    pub data: CmdKeyValuesData<'a>,
+}
+
+/// Payload of a `CmdKeyValues` message
+///
+/// Payloads that can't be fully decoded as binary KeyValues are kept raw.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "'a: 'static"))]
+#[serde(tag = "type", content = "data")]
+pub enum CmdKeyValuesData<'a> {
+    KeyValues(Vec<KeyValues>),
+    Unknown(Stream<'a>),
+}
+
+impl<'a> CmdKeyValuesData<'a> {
+    fn read(data: Stream<'a>) -> Self {
+        let mut body = data.clone();
+        match read_key_values(&mut body) {
+            Ok(key_values) if body.bits_left() == 0 => CmdKeyValuesData::KeyValues(key_values),
+            _ => CmdKeyValuesData::Unknown(data),
+        }
+    }
+}
+
+impl CmdKeyValuesMessage<'_> {
+    /// The decoded keys, empty if the payload couldn't be decoded
+    pub fn key_values(&self) -> &[KeyValues] {
+        match &self.data {
+            CmdKeyValuesData::KeyValues(key_values) => key_values,
+            CmdKeyValuesData::Unknown(_) => &[],
+        }
+    }
+
+    /// The root key, its name is the command that was sent
+    pub fn root(&self) -> Option<&KeyValues> {
+        self.key_values().first()
+    }
+
+    /// Name of the command that was sent
+    pub fn command(&self) -> Option<&str> {
+        self.root().map(|root| root.name.as_str())
+    }
+
+    /// Find a key by a `/` separated path, starting with the name of the root key
+    pub fn find(&self, path: &str) -> Option<&KeyValues> {
+        let (root, rest) = match path.split_once('/') {
+            Some((root, rest)) => (root, Some(rest)),
+            None => (path, None),
+        };
+        let root = self
+            .key_values()
+            .iter()
+            .find(|key| key.name.eq_ignore_ascii_case(root))?;
+        match rest {
+            Some(rest) => root.find(rest),
+            None => Some(root),
+        }
+    }
+}
+
+impl<'a> BitRead<'a, LittleEndian> for CmdKeyValuesMessage<'a> {
+    fn read(stream: &mut Stream<'a>) -> ReadResult<Self> {
+        let length: u32 = stream.read()?;
+        let data = stream.read_bits(length.saturating_mul(8) as usize)?;
+        Ok(CmdKeyValuesMessage {
+            data: CmdKeyValuesData::read(data),
+        })
+    }
+
+    fn skip(stream: &mut Stream<'a>) -> ReadResult<()> {
+        let length: u32 = stream.read()?;
+        stream.skip_bits(length.saturating_mul(8) as usize)
+    }
+}
+
+impl BitWrite<LittleEndian> for CmdKeyValuesMessage<'_> {
+    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+        stream.reserve_byte_length(32, |stream| match &self.data {
+            CmdKeyValuesData::KeyValues(key_values) => write_key_values(key_values, stream),
+            CmdKeyValuesData::Unknown(data) => data.write(stream),
+        })
+    }
+}
+
+#[test]
+fn test_cmd_key_values_roundtrip() {
+    use crate::demo::keyvalues::KeyValue;
+
+    crate::test_roundtrip_write(CmdKeyValuesMessage {
+        data: CmdKeyValuesData::KeyValues(Vec::new()),
+    });
+    crate::test_roundtrip_write(CmdKeyValuesMessage {
+        data: CmdKeyValuesData::KeyValues(vec![KeyValues::new(
+            "+use_action_slot_item_server",
+            KeyValue::Section(Vec::new()),
+        )]),
+    });
+
+    let message = CmdKeyValuesMessage {
+        data: CmdKeyValuesData::KeyValues(vec![KeyValues::new(
+            "MVM_Upgrade",
+            KeyValue::Section(vec![KeyValues::new(
+                "Upgrade",
+                KeyValue::Section(vec![
+                    KeyValues::new("itemslot", KeyValue::Int(-1)),
+                    KeyValues::new("Upgrade", KeyValue::Int(19)),
+                    KeyValues::new("count", KeyValue::Int(1)),
+                    KeyValues::new("name", KeyValue::String("upgrade".into())),
+                    KeyValues::new("scale", KeyValue::Float(0.5)),
+                    KeyValues::new("ptr", KeyValue::Pointer(12)),
+                    KeyValues::new("wide", KeyValue::WideString),
+                    KeyValues::new("color", KeyValue::Color([1, 2, 3, 4])),
+                    KeyValues::new("id", KeyValue::UInt64(76561198000000000)),
+                ]),
+            )]),
+        )]),
+    };
+    assert_eq!(Some("MVM_Upgrade"), message.command());
+    assert_eq!(
+        Some(19),
+        message
+            .find("mvm_upgrade/Upgrade/Upgrade")
+            .and_then(|key| key.value.as_int())
+    );
+    assert_eq!(None, message.find("Other/Upgrade"));
+    crate::test_roundtrip_write(message);
+}
+
+#[test]
+fn test_cmd_key_values_unknown() {
+    use bitbuffer::BitReadBuffer;
+
+    // a key with unknown type 12, followed by the end marker
+    let payload = [12, b'k', 0, 8];
+    let mut bytes = (payload.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(&payload);
+
+    let message: CmdKeyValuesMessage =
+        Stream::from(BitReadBuffer::new_owned(bytes.clone(), LittleEndian))
+            .read()
+            .unwrap();
+    assert!(matches!(message.data, CmdKeyValuesData::Unknown(_)));
+    assert_eq!(None, message.command());
+
+    let mut written = Vec::new();
+    message
+        .write(&mut BitWriteStream::new(&mut written, LittleEndian))
+        .unwrap();
+    assert_eq!(bytes, written);
+
+    // trailing data after the end marker is kept too
+    let payload = [8, 8];
+    let mut bytes = (payload.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(&payload);
+    let message: CmdKeyValuesMessage = Stream::from(BitReadBuffer::new_owned(bytes, LittleEndian))
+        .read()
+        .unwrap();
+    assert!(matches!(message.data, CmdKeyValuesData::Unknown(_)));
+}
@@ -0,0 +1,170 @@
+use bitbuffer::{BitWrite, BitWriteSized, BitWriteStream, LittleEndian};
+use serde::{Deserialize, Serialize};
+
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::packet::datatable::{ServerClass, ServerClassName};
+use crate::demo::parser::{Encode, ParseBitSkip};
+use crate::{Parse, ParseError, ParserState, Result, Stream};
+
+/// `BASEENTITY_MSG_REMOVE_DECALS`
+const REMOVE_DECALS: u8 = 1;
+
+/// Message sent to a single entity
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(bound(deserialize = "'a: 'static"))]
+pub struct EntityMessage<'a> {
+    pub index: u16,
+    pub class_id: u16,
+    pub data: EntityMessageData<'a>,
+}
+
+/// Body of an entity message
+///
+/// The layout of the body is defined by the class of the receiving entity, none of the tf2 classes
+/// override the base entity handler so any message to a known class is decoded with it.
+/// Messages to unknown classes or with an unknown layout are kept raw.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(bound(deserialize = "'a: 'static"))]
+#[serde(tag = "type", content = "data")]
+pub enum EntityMessageData<'a> {
+    /// Remove all decals from the entity
+    RemoveDecals,
+    Unknown(Stream<'a>),
+}
+
+impl<'a> EntityMessageData<'a> {
+    fn read(data: Stream<'a>, class: Option<&ServerClass>) -> Self {
+        if class.is_some() && data.bit_len() == 8 {
+            let mut body = data.clone();
+            if body.read::<u8>().ok() == Some(REMOVE_DECALS) {
+                return EntityMessageData::RemoveDecals;
+            }
+        }
+        EntityMessageData::Unknown(data)
+    }
+}
+
+impl EntityMessage<'_> {
+    pub fn entity_index(&self) -> EntityId {
+        EntityId::from(self.index as u32)
+    }
+
+    /// The server class of the entity the message is sent to
+    pub fn server_class<'s>(&self, state: &'s ParserState) -> Option<&'s ServerClass> {
+        state.server_classes.get(self.class_id as usize)
+    }
+
+    /// Name of the server class of the entity the message is sent to
+    pub fn class_name<'s>(&self, state: &'s ParserState) -> Option<&'s ServerClassName> {
+        self.server_class(state).map(|class| &class.name)
+    }
+}
+
+impl<'a> Parse<'a> for EntityMessage<'a> {
+    fn parse(stream: &mut Stream<'a>, state: &ParserState) -> Result<Self> {
+        let index: u16 = stream.read_sized(11)?;
+        let class_id: u16 = stream.read_sized(9)?;
+        let length: u16 = stream.read_sized(11)?;
+        let data = stream.read_bits(length as usize)?;
+        let data = EntityMessageData::read(data, state.server_classes.get(class_id as usize));
+        Ok(EntityMessage {
+            index,
+            class_id,
+            data,
+        })
+    }
+}
+
+impl ParseBitSkip<'_> for EntityMessage<'_> {
+    fn parse_skip(stream: &mut Stream, _state: &ParserState) -> Result<()> {
+        stream.skip_bits(20)?;
+        let length: u16 = stream.read_sized(11)?;
+        stream.skip_bits(length as usize).map_err(ParseError::from)
+    }
+}
+
+impl Encode for EntityMessage<'_> {
+    fn encode(
+        &self,
+        stream: &mut BitWriteStream<LittleEndian>,
+        _state: &ParserState,
+    ) -> Result<()> {
+        self.index.write_sized(stream, 11)?;
+        self.class_id.write_sized(stream, 9)?;
+        stream.reserve_length(11, |stream| match &self.data {
+            EntityMessageData::RemoveDecals => REMOVE_DECALS.write(stream),
+            EntityMessageData::Unknown(data) => data.write(stream),
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_state() -> ParserState {
+    let mut state = ParserState::new(24, |_| false, false);
+    state.server_classes = vec![
+        ServerClass {
+            id: 0u16.into(),
+            name: "CTFPlayer".into(),
+            data_table: "DT_TFPlayer".into(),
+        },
+        ServerClass {
+            id: 1u16.into(),
+            name: "CTFWeaponMedigun".into(),
+            data_table: "DT_WeaponMedigun".into(),
+        },
+    ];
+    state
+}
+
+#[cfg(test)]
+fn parse_message(bytes: Vec<u8>, state: &ParserState) -> EntityMessage<'static> {
+    use bitbuffer::BitReadBuffer;
+
+    let mut stream = Stream::new(BitReadBuffer::new_owned(bytes, LittleEndian));
+    EntityMessage::parse(&mut stream, state).unwrap()
+}
+
+#[cfg(test)]
+fn encode_message(message: &EntityMessage, state: &ParserState) -> Vec<u8> {
+    let mut data = Vec::new();
+    {
+        let mut stream = BitWriteStream::new(&mut data, LittleEndian);
+        message.encode(&mut stream, state).unwrap();
+    }
+    data
+}
+
+#[test]
+fn test_entity_message_class() {
+    let state = test_state();
+
+    // entity 12, class 1, 16 bits of data
+    let bytes = vec![0x0c, 0x08, 0x00, 0x81, 0x00, 0x01];
+    let message = parse_message(bytes.clone(), &state);
+    assert_eq!(EntityId::from(12u32), message.entity_index());
+    assert_eq!(
+        Some("CTFWeaponMedigun"),
+        message.class_name(&state).map(|name| name.as_str())
+    );
+    assert!(matches!(message.data, EntityMessageData::Unknown(_)));
+    assert_eq!(bytes, encode_message(&message, &state));
+}
+
+#[test]
+fn test_remove_decals() {
+    let state = test_state();
+
+    // `CBaseEntity::RemoveAllDecals` for player 3: a single `BASEENTITY_MSG_REMOVE_DECALS` byte
+    let bytes = vec![0x03, 0x00, 0x80, 0x80, 0x00];
+    let message = parse_message(bytes.clone(), &state);
+    assert_eq!(EntityId::from(3u32), message.entity_index());
+    assert_eq!(EntityMessageData::RemoveDecals, message.data);
+    assert_eq!(bytes, encode_message(&message, &state));
+
+    // the class isn't known yet, keep it raw
+    let message = parse_message(bytes, &ParserState::new(24, |_| false, false));
+    assert!(matches!(message.data, EntityMessageData::Unknown(_)));
+}
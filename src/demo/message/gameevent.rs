@@ -154,6 +154,12 @@ impl From<GameEventTypeId> for u16 {
     }
 }
 
+impl From<u16> for GameEventTypeId {
+    fn from(id: u16) -> Self {
+        GameEventTypeId(id)
+    }
+}
+
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct GameEventListMessage {
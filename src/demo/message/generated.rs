@@ -1,7 +1,6 @@
 /// Messages that consists only of primitives and string and can be derived
 use crate::demo::data::{MaybeUtf8String, ServerTick};
-use crate::Stream;
-use bitbuffer::{BitRead, BitWrite, LittleEndian};
+use bitbuffer::{BitRead, BitWrite};
 use serde::{Deserialize, Serialize};
 
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -97,21 +96,6 @@ pub struct FixAngleMessage {
     pub z: u16,
 }
 
-#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
-#[derive(BitRead, BitWrite, Debug, PartialEq, Serialize, Deserialize, Clone)]
-#[endianness = "LittleEndian"]
-#[serde(bound(deserialize = "'a: 'static"))]
-pub struct EntityMessage<'a> {
-    #[size = 11]
-    pub index: u16,
-    #[size = 9]
-    pub class_id: u16,
-    #[size = 11]
-    pub length: u16,
-    #[size = "length"]
-    pub data: Stream<'a>,
-}
-
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(BitRead, BitWrite, Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct PreFetchMessage {
@@ -119,30 +103,9 @@ pub struct PreFetchMessage {
     pub index: u16,
 }
 
-#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
-#[derive(BitRead, BitWrite, Debug, PartialEq, Serialize, Deserialize, Clone)]
-#[endianness = "LittleEndian"]
-#[serde(bound(deserialize = "'a: 'static"))]
-pub struct MenuMessage<'a> {
-    pub kind: u16,
-    pub length: u16,
-    #[size = "length.saturating_mul(8)"]
-    pub index: Stream<'a>,
-}
-
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(BitRead, BitWrite, Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct GetCvarValueMessage {
     pub cookie: u32,
     pub value: String,
 }
-
-#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
-#[derive(BitRead, BitWrite, Debug, PartialEq, Serialize, Deserialize, Clone)]
-#[endianness = "LittleEndian"]
-#[serde(bound(deserialize = "'a: 'static"))]
-pub struct CmdKeyValuesMessage<'a> {
-    pub length: u32,
-    #[size = "length.saturating_mul(8)"]
-    pub data: Stream<'a>,
-}
@@ -0,0 +1,230 @@
+use bitbuffer::{BitRead, BitReadStream, BitWrite, BitWriteStream, Endianness, LittleEndian};
+use serde::{Deserialize, Serialize};
+
+use crate::demo::keyvalues::{read_key_values, write_key_values, KeyValues};
+use crate::{ReadResult, Stream};
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DialogType {
+    // Just an on screen message
+    Message,
+    // A menu with options
+    Menu,
+    // A richtext dialog
+    Text,
+    // An entry box
+    Entry,
+    // Asks the client to connect to a specified IP address
+    AskConnect,
+    Unknown(u16),
+}
+
+impl From<u16> for DialogType {
+    fn from(raw: u16) -> Self {
+        match raw {
+            0 => DialogType::Message,
+            1 => DialogType::Menu,
+            2 => DialogType::Text,
+            3 => DialogType::Entry,
+            4 => DialogType::AskConnect,
+            raw => DialogType::Unknown(raw),
+        }
+    }
+}
+
+impl From<DialogType> for u16 {
+    fn from(kind: DialogType) -> Self {
+        match kind {
+            DialogType::Message => 0,
+            DialogType::Menu => 1,
+            DialogType::Text => 2,
+            DialogType::Entry => 3,
+            DialogType::AskConnect => 4,
+            DialogType::Unknown(raw) => raw,
+        }
+    }
+}
+
+impl<'a, E: Endianness> BitRead<'a, E> for DialogType {
+    fn read(stream: &mut BitReadStream<'a, E>) -> ReadResult<Self> {
+        stream.read::<u16>().map(DialogType::from)
+    }
+
+    fn skip(stream: &mut BitReadStream<'a, E>) -> ReadResult<()> {
+        stream.skip_bits(16)
+    }
+}
+
+impl<E: Endianness> BitWrite<E> for DialogType {
+    fn write(&self, stream: &mut BitWriteStream<E>) -> ReadResult<()> {
+        u16::from(*self).write(stream)
+    }
+}
+
+/// Plugin menu or dialog shown to the player, the contents are defined by the keys
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "'a: 'static"))]
+pub struct MenuMessage<'a> {
+    pub kind: DialogType,
+    pub data: MenuData<'a>,
+}
+
+/// Payload of a `Menu` message
+///
+/// Payloads that can't be fully decoded as binary KeyValues are kept raw.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "'a: 'static"))]
+#[serde(tag = "type", content = "data")]
+pub enum MenuData<'a> {
+    KeyValues(Vec<KeyValues>),
+    Unknown(Stream<'a>),
+}
+
+impl<'a> MenuData<'a> {
+    fn read(data: Stream<'a>) -> Self {
+        let mut body = data.clone();
+        match read_key_values(&mut body) {
+            Ok(key_values) if body.bits_left() == 0 => MenuData::KeyValues(key_values),
+            _ => MenuData::Unknown(data),
+        }
+    }
+}
+
+impl MenuMessage<'_> {
+    /// The decoded keys, empty if the payload couldn't be decoded
+    pub fn key_values(&self) -> &[KeyValues] {
+        match &self.data {
+            MenuData::KeyValues(key_values) => key_values,
+            MenuData::Unknown(_) => &[],
+        }
+    }
+
+    /// Find a key by a `/` separated path, e.g. `"title"` or `"1/msg"`
+    pub fn find(&self, path: &str) -> Option<&KeyValues> {
+        let (root, rest) = match path.split_once('/') {
+            Some((root, rest)) => (root, Some(rest)),
+            None => (path, None),
+        };
+        // the menu keys are sent as children of a single unnamed root key
+        let key = self
+            .key_values()
+            .iter()
+            .flat_map(KeyValues::children)
+            .find(|key| key.name.eq_ignore_ascii_case(root))?;
+        match rest {
+            Some(rest) => key.find(rest),
+            None => Some(key),
+        }
+    }
+}
+
+impl<'a> BitRead<'a, LittleEndian> for MenuMessage<'a> {
+    fn read(stream: &mut Stream<'a>) -> ReadResult<Self> {
+        let kind = stream.read()?;
+        let length: u16 = stream.read()?;
+        let data = stream.read_bits(length as usize * 8)?;
+        Ok(MenuMessage {
+            kind,
+            data: MenuData::read(data),
+        })
+    }
+
+    fn skip(stream: &mut Stream<'a>) -> ReadResult<()> {
+        stream.skip_bits(16)?;
+        let length: u16 = stream.read()?;
+        stream.skip_bits(length as usize * 8)
+    }
+}
+
+impl BitWrite<LittleEndian> for MenuMessage<'_> {
+    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+        self.kind.write(stream)?;
+        stream.reserve_byte_length(16, |stream| match &self.data {
+            MenuData::KeyValues(key_values) => write_key_values(key_values, stream),
+            MenuData::Unknown(data) => data.write(stream),
+        })
+    }
+}
+
+#[test]
+fn test_menu_roundtrip() {
+    use crate::demo::keyvalues::KeyValue;
+
+    let message = MenuMessage {
+        kind: DialogType::Menu,
+        data: MenuData::KeyValues(vec![KeyValues::new(
+            "menu",
+            KeyValue::Section(vec![
+                KeyValues::new("title", KeyValue::String("Vote map".into())),
+                KeyValues::new("level", KeyValue::Int(1)),
+                KeyValues::new("time", KeyValue::Int(20)),
+                KeyValues::new(
+                    "1",
+                    KeyValue::Section(vec![
+                        KeyValues::new("msg", KeyValue::String("cp_badlands".into())),
+                        KeyValues::new("command", KeyValue::String("vote 1".into())),
+                    ]),
+                ),
+            ]),
+        )]),
+    };
+    assert_eq!(
+        Some("Vote map"),
+        message.find("title").and_then(|key| key.value.as_str())
+    );
+    assert_eq!(
+        Some("cp_badlands"),
+        message.find("1/msg").and_then(|key| key.value.as_str())
+    );
+    assert_eq!(None, message.find("2/msg"));
+    crate::test_roundtrip_write(message);
+}
+
+#[test]
+fn test_unknown_dialog_type() {
+    assert_eq!(DialogType::AskConnect, DialogType::from(4));
+    assert_eq!(DialogType::Unknown(7), DialogType::from(7));
+    assert_eq!(7, u16::from(DialogType::Unknown(7)));
+
+    crate::test_roundtrip_write(MenuMessage {
+        kind: DialogType::Unknown(7),
+        data: MenuData::KeyValues(Vec::new()),
+    });
+}
+
+#[test]
+fn test_menu_unknown_data() {
+    use bitbuffer::BitReadBuffer;
+
+    // a key with unknown type 12, followed by the end marker
+    let payload = [12, b'k', 0, 8];
+    let mut bytes = 1u16.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    let message: MenuMessage = Stream::from(BitReadBuffer::new_owned(bytes.clone(), LittleEndian))
+        .read()
+        .unwrap();
+    assert_eq!(DialogType::Menu, message.kind);
+    assert!(matches!(message.data, MenuData::Unknown(_)));
+    assert_eq!(None, message.find("title"));
+
+    let mut written = Vec::new();
+    message
+        .write(&mut BitWriteStream::new(&mut written, LittleEndian))
+        .unwrap();
+    assert_eq!(bytes, written);
+
+    // trailing data after the end marker is kept too
+    let payload = [8, 8];
+    let mut bytes = 1u16.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    let message: MenuMessage = Stream::from(BitReadBuffer::new_owned(bytes, LittleEndian))
+        .read()
+        .unwrap();
+    assert!(matches!(message.data, MenuData::Unknown(_)));
+}
@@ -2,7 +2,10 @@ pub use generated::*;
 
 use crate::demo::message::bspdecal::*;
 use crate::demo::message::classinfo::*;
+use crate::demo::message::cmdkeyvalues::*;
+use crate::demo::message::entitymessage::*;
 use crate::demo::message::gameevent::*;
+use crate::demo::message::menu::*;
 use crate::demo::message::packetentities::*;
 use crate::demo::message::setconvar::*;
 use crate::demo::message::stringtable::*;
@@ -17,8 +20,11 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 
 pub mod bspdecal;
 pub mod classinfo;
+pub mod cmdkeyvalues;
+pub mod entitymessage;
 pub mod gameevent;
 pub mod generated;
+pub mod menu;
 pub mod packetentities;
 pub mod setconvar;
 pub mod stringtable;
@@ -82,7 +88,7 @@ pub enum Message<'a> {
     UpdateStringTable(UpdateStringTableMessage<'a>),
     VoiceInit(VoiceInitMessage),
     VoiceData(VoiceDataMessage<'a>),
-    ParseSounds(ParseSoundsMessage<'a>),
+    ParseSounds(ParseSoundsMessage),
     SetView(SetViewMessage),
     FixAngle(FixAngleMessage),
     BspDecal(BSPDecalMessage),
@@ -63,6 +63,18 @@ impl From<EntityId> for usize {
     }
 }
 
+impl<E: Endianness> BitReadSized<'_, E> for EntityId {
+    fn read(stream: &mut BitReadStream<'_, E>, size: usize) -> ReadResult<Self> {
+        Ok(EntityId(stream.read_sized(size)?))
+    }
+}
+
+impl<E: Endianness> BitWriteSized<E> for EntityId {
+    fn write_sized(&self, stream: &mut BitWriteStream<E>, len: usize) -> ReadResult<()> {
+        self.0.write_sized(stream, len)
+    }
+}
+
 impl PartialEq<u32> for EntityId {
     fn eq(&self, other: &u32) -> bool {
         self.0 == *other
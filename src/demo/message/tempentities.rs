@@ -1,9 +1,11 @@
 use super::stringtable::read_var_int;
+use crate::demo::message::packetentities::EntityId;
 use crate::demo::message::packetentities::PacketEntitiesMessage;
 use crate::demo::message::stringtable::{encode_var_int_fixed, log_base2};
-use crate::demo::packet::datatable::ClassId;
+use crate::demo::packet::datatable::{ClassId, ServerClassName};
 use crate::demo::parser::{Encode, ParseBitSkip};
-use crate::demo::sendprop::SendProp;
+use crate::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
+use crate::demo::vector::Vector;
 use crate::Result;
 use crate::{Parse, ParseError, ParserState, Stream};
 use bitbuffer::{BitWrite, BitWriteSized, BitWriteStream, LittleEndian};
@@ -138,3 +140,444 @@ impl Encode for TempEntitiesMessage {
         Ok(())
     }
 }
+
+impl EventInfo {
+    /// Name of the server class of the temp entity, e.g. `CTEFireBullets`
+    pub fn class_name<'s>(&self, state: &'s ParserState) -> Option<&'s ServerClassName> {
+        state
+            .server_classes
+            .get(usize::from(self.class_id))
+            .map(|class| &class.name)
+    }
+
+    /// Decode the props of a known temp entity type
+    ///
+    /// Returns `None` for temp entities without a typed representation
+    pub fn decode(&self, state: &ParserState) -> Option<TempEntity> {
+        let props = self.props.as_slice();
+        Some(match self.class_name(state)?.as_str() {
+            "CTEFireBullets" => TempEntity::FireBullets(FireBulletsEvent::from_props(props)),
+            "CTETFExplosion" => TempEntity::Explosion(ExplosionEvent::from_props(props)),
+            "CTETFBlood" => TempEntity::Blood(BloodEvent::from_props(props)),
+            "CTETFParticleEffect" => {
+                TempEntity::ParticleEffect(ParticleEffectEvent::from_props(props))
+            }
+            "CTEPlayerDecal" => TempEntity::PlayerDecal(PlayerDecalEvent::from_props(props)),
+            _ => return None,
+        })
+    }
+}
+
+/// The common TF2 temp entities
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TempEntity {
+    FireBullets(FireBulletsEvent),
+    Explosion(ExplosionEvent),
+    Blood(BloodEvent),
+    ParticleEffect(ParticleEffectEvent),
+    PlayerDecal(PlayerDecalEvent),
+}
+
+fn int_value(value: &SendPropValue) -> i64 {
+    i64::try_from(value).unwrap_or_default()
+}
+
+fn float_value(value: &SendPropValue) -> f32 {
+    f32::try_from(value).unwrap_or_default()
+}
+
+fn vector_value(value: &SendPropValue) -> Vector {
+    Vector::try_from(value).unwrap_or_default()
+}
+
+/// Entity index props are sent as -1 when no entity is set
+fn entity_value(value: &SendPropValue) -> Option<EntityId> {
+    u32::try_from(int_value(value)).ok().map(EntityId::from)
+}
+
+/// Hitscan shot fired by a player
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FireBulletsEvent {
+    /// Entity index of the shooting player, if any
+    pub player: Option<EntityId>,
+    pub origin: Vector,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub weapon_id: u16,
+    /// Primary or secondary attack
+    pub mode: u8,
+    /// Random seed for the bullet spread
+    pub seed: u16,
+    pub spread: f32,
+    pub critical: bool,
+}
+
+impl FireBulletsEvent {
+    fn from_props(props: &[SendProp]) -> Self {
+        const ORIGIN: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_vecOrigin");
+        const PITCH: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_vecAngles[0]");
+        const YAW: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_vecAngles[1]");
+        const WEAPON_ID: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_iWeaponID");
+        const MODE: SendPropIdentifier = SendPropIdentifier::new("DT_TEFireBullets", "m_iMode");
+        const SEED: SendPropIdentifier = SendPropIdentifier::new("DT_TEFireBullets", "m_iSeed");
+        const PLAYER: SendPropIdentifier = SendPropIdentifier::new("DT_TEFireBullets", "m_iPlayer");
+        const SPREAD: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_flSpread");
+        const CRITICAL: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEFireBullets", "m_bCritical");
+
+        // the player is sent as entity index - 1, with -1 when the shot wasn't fired by a player
+        let mut event = FireBulletsEvent {
+            player: Some(EntityId::from(1u32)),
+            ..FireBulletsEvent::default()
+        };
+        for prop in props {
+            match prop.identifier {
+                ORIGIN => event.origin = vector_value(&prop.value),
+                PITCH => event.pitch = float_value(&prop.value),
+                YAW => event.yaw = float_value(&prop.value),
+                WEAPON_ID => event.weapon_id = int_value(&prop.value) as u16,
+                MODE => event.mode = int_value(&prop.value) as u8,
+                SEED => event.seed = int_value(&prop.value) as u16,
+                PLAYER => {
+                    event.player = u32::try_from(int_value(&prop.value))
+                        .ok()
+                        .and_then(|index| index.checked_add(1))
+                        .map(EntityId::from)
+                }
+                SPREAD => event.spread = float_value(&prop.value),
+                CRITICAL => event.critical = int_value(&prop.value) > 0,
+                _ => {}
+            }
+        }
+        event
+    }
+}
+
+/// Explosion of a projectile or other explosive
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExplosionEvent {
+    pub origin: Vector,
+    pub normal: Vector,
+    pub weapon_id: u16,
+    /// The entity that was hit directly
+    pub entity: Option<EntityId>,
+    /// Item definition index of the weapon
+    pub item_definition: i32,
+    pub sound: i32,
+    pub custom_particle: i32,
+}
+
+impl ExplosionEvent {
+    fn from_props(props: &[SendProp]) -> Self {
+        const ORIGIN_X: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_vecOrigin[0]");
+        const ORIGIN_Y: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_vecOrigin[1]");
+        const ORIGIN_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_vecOrigin[2]");
+        const NORMAL: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_vecNormal");
+        const WEAPON_ID: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_iWeaponID");
+        const ENTITY: SendPropIdentifier = SendPropIdentifier::new("DT_TETFExplosion", "entindex");
+        const DEFINITION: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_nDefID");
+        const SOUND: SendPropIdentifier = SendPropIdentifier::new("DT_TETFExplosion", "m_nSound");
+        const PARTICLE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFExplosion", "m_iCustomParticleIndex");
+
+        let mut event = ExplosionEvent::default();
+        for prop in props {
+            match prop.identifier {
+                ORIGIN_X => event.origin.x = float_value(&prop.value),
+                ORIGIN_Y => event.origin.y = float_value(&prop.value),
+                ORIGIN_Z => event.origin.z = float_value(&prop.value),
+                NORMAL => event.normal = vector_value(&prop.value),
+                WEAPON_ID => event.weapon_id = int_value(&prop.value) as u16,
+                ENTITY => event.entity = entity_value(&prop.value),
+                DEFINITION => event.item_definition = int_value(&prop.value) as i32,
+                SOUND => event.sound = int_value(&prop.value) as i32,
+                PARTICLE => event.custom_particle = int_value(&prop.value) as i32,
+                _ => {}
+            }
+        }
+        event
+    }
+}
+
+/// Blood effect from a player being hit
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BloodEvent {
+    pub origin: Vector,
+    pub normal: Vector,
+    /// The entity that is bleeding
+    pub entity: Option<EntityId>,
+}
+
+impl BloodEvent {
+    fn from_props(props: &[SendProp]) -> Self {
+        const ORIGIN_X: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFBlood", "m_vecOrigin[0]");
+        const ORIGIN_Y: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFBlood", "m_vecOrigin[1]");
+        const ORIGIN_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFBlood", "m_vecOrigin[2]");
+        const NORMAL: SendPropIdentifier = SendPropIdentifier::new("DT_TETFBlood", "m_vecNormal");
+        const ENTITY: SendPropIdentifier = SendPropIdentifier::new("DT_TETFBlood", "entindex");
+
+        let mut event = BloodEvent::default();
+        for prop in props {
+            match prop.identifier {
+                ORIGIN_X => event.origin.x = float_value(&prop.value),
+                ORIGIN_Y => event.origin.y = float_value(&prop.value),
+                ORIGIN_Z => event.origin.z = float_value(&prop.value),
+                NORMAL => event.normal = vector_value(&prop.value),
+                ENTITY => event.entity = entity_value(&prop.value),
+                _ => {}
+            }
+        }
+        event
+    }
+}
+
+/// Particle effect, the name of the particle system can be found in the `ParticleEffectNames`
+/// string table
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParticleEffectEvent {
+    pub origin: Vector,
+    pub start: Vector,
+    pub angles: Vector,
+    pub particle_system: u16,
+    /// The entity the effect is attached to
+    pub entity: Option<EntityId>,
+    pub attach_type: u8,
+    pub attachment_point: u8,
+    pub reset_particles: bool,
+}
+
+impl ParticleEffectEvent {
+    fn from_props(props: &[SendProp]) -> Self {
+        const ORIGIN_X: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecOrigin[0]");
+        const ORIGIN_Y: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecOrigin[1]");
+        const ORIGIN_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecOrigin[2]");
+        const START_X: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecStart[0]");
+        const START_Y: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecStart[1]");
+        const START_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecStart[2]");
+        const ANGLES: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_vecAngles");
+        const PARTICLE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_iParticleSystemIndex");
+        const ENTITY: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "entindex");
+        const ATTACH_TYPE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_iAttachType");
+        const ATTACHMENT: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_iAttachmentPointIndex");
+        const RESET: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TETFParticleEffect", "m_bResetParticles");
+
+        let mut event = ParticleEffectEvent::default();
+        for prop in props {
+            match prop.identifier {
+                ORIGIN_X => event.origin.x = float_value(&prop.value),
+                ORIGIN_Y => event.origin.y = float_value(&prop.value),
+                ORIGIN_Z => event.origin.z = float_value(&prop.value),
+                START_X => event.start.x = float_value(&prop.value),
+                START_Y => event.start.y = float_value(&prop.value),
+                START_Z => event.start.z = float_value(&prop.value),
+                ANGLES => event.angles = vector_value(&prop.value),
+                PARTICLE => event.particle_system = int_value(&prop.value) as u16,
+                ENTITY => event.entity = entity_value(&prop.value),
+                ATTACH_TYPE => event.attach_type = int_value(&prop.value) as u8,
+                ATTACHMENT => event.attachment_point = int_value(&prop.value) as u8,
+                RESET => event.reset_particles = int_value(&prop.value) > 0,
+                _ => {}
+            }
+        }
+        event
+    }
+}
+
+/// Spray applied by a player
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerDecalEvent {
+    /// Entity index of the spraying player
+    pub player: EntityId,
+    pub origin: Vector,
+    /// The entity the spray was applied to, the world is entity 0
+    pub entity: EntityId,
+}
+
+impl PlayerDecalEvent {
+    fn from_props(props: &[SendProp]) -> Self {
+        const ORIGIN: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TEPlayerDecal", "m_vecOrigin");
+        const ENTITY: SendPropIdentifier = SendPropIdentifier::new("DT_TEPlayerDecal", "m_nEntity");
+        const PLAYER: SendPropIdentifier = SendPropIdentifier::new("DT_TEPlayerDecal", "m_nPlayer");
+
+        let mut event = PlayerDecalEvent::default();
+        for prop in props {
+            match prop.identifier {
+                ORIGIN => event.origin = vector_value(&prop.value),
+                ENTITY => event.entity = entity_value(&prop.value).unwrap_or_default(),
+                PLAYER => event.player = entity_value(&prop.value).unwrap_or_default(),
+                _ => {}
+            }
+        }
+        event
+    }
+}
+
+#[test]
+fn test_decode_temp_entities() {
+    use crate::demo::packet::datatable::ServerClass;
+
+    let mut state = ParserState::new(24, |_| false, false);
+    state.server_classes = [
+        "CTEFireBullets",
+        "CTETFExplosion",
+        "CTEBubbles",
+        "CTEPlayerDecal",
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, name)| ServerClass {
+        id: (id as u16).into(),
+        name: name.into(),
+        data_table: name.replace("CTE", "DT_TE").into(),
+    })
+    .collect();
+    let prop = |table: &str, name: &str, value: SendPropValue| SendProp {
+        index: 0,
+        identifier: SendPropIdentifier::new(table, name),
+        value,
+    };
+    let event = |class_id: u16, props: Vec<SendProp>| EventInfo {
+        class_id: class_id.into(),
+        fire_delay: 0.0,
+        reliable: false,
+        props,
+    };
+
+    let bullets = event(
+        0,
+        vec![
+            prop(
+                "DT_TEFireBullets",
+                "m_vecOrigin",
+                Vector {
+                    x: 1.0,
+                    y: 2.0,
+                    z: 3.0,
+                }
+                .into(),
+            ),
+            prop("DT_TEFireBullets", "m_vecAngles[1]", 90.0.into()),
+            prop("DT_TEFireBullets", "m_iWeaponID", 10.into()),
+            prop("DT_TEFireBullets", "m_iPlayer", 4.into()),
+            prop("DT_TEFireBullets", "m_bCritical", 1.into()),
+        ],
+    );
+    assert_eq!(
+        "CTEFireBullets",
+        bullets.class_name(&state).unwrap().as_str()
+    );
+    assert_eq!(
+        Some(TempEntity::FireBullets(FireBulletsEvent {
+            player: Some(5u32.into()),
+            origin: Vector {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            yaw: 90.0,
+            weapon_id: 10,
+            critical: true,
+            ..FireBulletsEvent::default()
+        })),
+        bullets.decode(&state)
+    );
+
+    let world_bullets = event(0, vec![prop("DT_TEFireBullets", "m_iPlayer", (-1).into())]);
+    assert_eq!(
+        Some(TempEntity::FireBullets(FireBulletsEvent {
+            player: None,
+            ..FireBulletsEvent::default()
+        })),
+        world_bullets.decode(&state)
+    );
+
+    let explosion = event(
+        1,
+        vec![
+            prop("DT_TETFExplosion", "m_vecOrigin[0]", 10.0.into()),
+            prop("DT_TETFExplosion", "m_vecOrigin[2]", (-5.0).into()),
+            prop("DT_TETFExplosion", "entindex", (-1).into()),
+            prop("DT_TETFExplosion", "m_nDefID", 18.into()),
+        ],
+    );
+    assert_eq!(
+        Some(TempEntity::Explosion(ExplosionEvent {
+            origin: Vector {
+                x: 10.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            entity: None,
+            item_definition: 18,
+            ..ExplosionEvent::default()
+        })),
+        explosion.decode(&state)
+    );
+
+    let spray = event(
+        3,
+        vec![
+            prop(
+                "DT_TEPlayerDecal",
+                "m_vecOrigin",
+                Vector {
+                    x: 100.0,
+                    y: -20.0,
+                    z: 64.0,
+                }
+                .into(),
+            ),
+            prop("DT_TEPlayerDecal", "m_nEntity", 0.into()),
+            prop("DT_TEPlayerDecal", "m_nPlayer", 7.into()),
+        ],
+    );
+    assert_eq!(
+        Some(TempEntity::PlayerDecal(PlayerDecalEvent {
+            player: 7u32.into(),
+            origin: Vector {
+                x: 100.0,
+                y: -20.0,
+                z: 64.0,
+            },
+            entity: 0u32.into(),
+        })),
+        spray.decode(&state)
+    );
+
+    assert_eq!(None, event(2, Vec::new()).decode(&state));
+    assert_eq!(None, event(4, Vec::new()).decode(&state));
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::demo::data::MaybeUtf8String;
 use crate::demo::message::packetentities::EntityId;
+use crate::demo::sendprop::{read_bit_vec3_coord, write_bit_vec3_coord};
+use crate::demo::vector::Vector;
 use crate::{ReadResult, Stream};
 
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -92,6 +94,26 @@ pub enum UserMessage<'a> {
     Rumble(RumbleMessage),
     Fade(FadeMessage),
     HapMeleeContact(HapMeleeContactMessage),
+    HudText(HudTextMessage),
+    HudMsg(Box<HudMsgMessage>),
+    HintText(HintTextMessage),
+    KeyHintText(KeyHintTextMessage),
+    Damage(DamageMessage),
+    PlayerIgnited(PlayerIgnitedMessage),
+    PlayerJarated(PlayerJaratedMessage),
+    PlayerExtinguished(PlayerExtinguishedMessage),
+    PlayerShieldBlocked(PlayerShieldBlockedMessage),
+    BreakModel(BreakModelMessage),
+    PlayerBonusPoints(PlayerBonusPointsMessage),
+    DamageDodged(DamageDodgedMessage),
+    AchievementEvent(AchievementEventMessage),
+    PlayerStatsUpdate(PlayerStatsUpdateMessage),
+    HudNotifyCustom(Box<HudNotifyCustomMessage>),
+    VoteStart(Box<VoteStartMessage>),
+    VotePass(Box<VotePassMessage>),
+    VoteFailed(VoteFailedMessage),
+    CallVoteFailed(CallVoteFailedMessage),
+    VoteSetup(VoteSetupMessage),
     Unknown(UnknownUserMessage<'a>),
 }
 
@@ -108,6 +130,26 @@ impl UserMessage<'_> {
             UserMessage::Rumble(_) => UserMessageType::Rumble as u8,
             UserMessage::Fade(_) => UserMessageType::Fade as u8,
             UserMessage::HapMeleeContact(_) => UserMessageType::HapMeleeContact as u8,
+            UserMessage::HudText(_) => UserMessageType::HudText as u8,
+            UserMessage::HudMsg(_) => UserMessageType::HudMsg as u8,
+            UserMessage::HintText(_) => UserMessageType::HintText as u8,
+            UserMessage::KeyHintText(_) => UserMessageType::KeyHintText as u8,
+            UserMessage::Damage(_) => UserMessageType::Damage as u8,
+            UserMessage::PlayerIgnited(_) => UserMessageType::PlayerIgnited as u8,
+            UserMessage::PlayerJarated(_) => UserMessageType::PlayerJarated as u8,
+            UserMessage::PlayerExtinguished(_) => UserMessageType::PlayerExtinguished as u8,
+            UserMessage::PlayerShieldBlocked(_) => UserMessageType::PlayerShieldBlocked as u8,
+            UserMessage::BreakModel(_) => UserMessageType::BreakModel as u8,
+            UserMessage::PlayerBonusPoints(_) => UserMessageType::PlayerBonusPoints as u8,
+            UserMessage::DamageDodged(_) => UserMessageType::DamageDodged as u8,
+            UserMessage::AchievementEvent(_) => UserMessageType::AchievementEvent as u8,
+            UserMessage::PlayerStatsUpdate(_) => UserMessageType::PlayerStatsUpdate as u8,
+            UserMessage::HudNotifyCustom(_) => UserMessageType::HudNotifyCustom as u8,
+            UserMessage::VoteStart(_) => UserMessageType::VoteStart as u8,
+            UserMessage::VotePass(_) => UserMessageType::VotePass as u8,
+            UserMessage::VoteFailed(_) => UserMessageType::VoteFailed as u8,
+            UserMessage::CallVoteFailed(_) => UserMessageType::CallVoteFailed as u8,
+            UserMessage::VoteSetup(_) => UserMessageType::VoteSetup as u8,
             UserMessage::Unknown(msg) => msg.raw_type,
         }
     }
@@ -130,6 +172,36 @@ impl<'a> BitRead<'a, LittleEndian> for UserMessage<'a> {
                     UserMessageType::Rumble => UserMessage::Rumble(data.read()?),
                     UserMessageType::Fade => UserMessage::Fade(data.read()?),
                     UserMessageType::HapMeleeContact => UserMessage::HapMeleeContact(data.read()?),
+                    UserMessageType::HudText => UserMessage::HudText(data.read()?),
+                    UserMessageType::HudMsg => UserMessage::HudMsg(data.read()?),
+                    UserMessageType::HintText => UserMessage::HintText(data.read()?),
+                    UserMessageType::KeyHintText => UserMessage::KeyHintText(data.read()?),
+                    UserMessageType::Damage => UserMessage::Damage(data.read()?),
+                    UserMessageType::PlayerIgnited => UserMessage::PlayerIgnited(data.read()?),
+                    UserMessageType::PlayerJarated => UserMessage::PlayerJarated(data.read()?),
+                    UserMessageType::PlayerExtinguished => {
+                        UserMessage::PlayerExtinguished(data.read()?)
+                    }
+                    UserMessageType::PlayerShieldBlocked => {
+                        UserMessage::PlayerShieldBlocked(data.read()?)
+                    }
+                    UserMessageType::BreakModel => UserMessage::BreakModel(data.read()?),
+                    UserMessageType::PlayerBonusPoints => {
+                        UserMessage::PlayerBonusPoints(data.read()?)
+                    }
+                    UserMessageType::DamageDodged => UserMessage::DamageDodged(data.read()?),
+                    UserMessageType::AchievementEvent => {
+                        UserMessage::AchievementEvent(data.read()?)
+                    }
+                    UserMessageType::PlayerStatsUpdate => {
+                        UserMessage::PlayerStatsUpdate(data.read()?)
+                    }
+                    UserMessageType::HudNotifyCustom => UserMessage::HudNotifyCustom(data.read()?),
+                    UserMessageType::VoteStart => UserMessage::VoteStart(data.read()?),
+                    UserMessageType::VotePass => UserMessage::VotePass(data.read()?),
+                    UserMessageType::VoteFailed => UserMessage::VoteFailed(data.read()?),
+                    UserMessageType::CallVoteFailed => UserMessage::CallVoteFailed(data.read()?),
+                    UserMessageType::VoteSetup => UserMessage::VoteSetup(data.read()?),
                     _ => UserMessage::Unknown(UnknownUserMessage {
                         raw_type: message_type as u8,
                         data,
@@ -171,6 +243,26 @@ impl<'a> BitWrite<LittleEndian> for UserMessage<'a> {
             UserMessage::Rumble(body) => stream.write(body),
             UserMessage::Fade(body) => stream.write(body),
             UserMessage::HapMeleeContact(body) => stream.write(body),
+            UserMessage::HudText(body) => stream.write(body),
+            UserMessage::HudMsg(body) => stream.write(body),
+            UserMessage::HintText(body) => stream.write(body),
+            UserMessage::KeyHintText(body) => stream.write(body),
+            UserMessage::Damage(body) => stream.write(body),
+            UserMessage::PlayerIgnited(body) => stream.write(body),
+            UserMessage::PlayerJarated(body) => stream.write(body),
+            UserMessage::PlayerExtinguished(body) => stream.write(body),
+            UserMessage::PlayerShieldBlocked(body) => stream.write(body),
+            UserMessage::BreakModel(body) => stream.write(body),
+            UserMessage::PlayerBonusPoints(body) => stream.write(body),
+            UserMessage::DamageDodged(body) => stream.write(body),
+            UserMessage::AchievementEvent(body) => stream.write(body),
+            UserMessage::PlayerStatsUpdate(body) => stream.write(body),
+            UserMessage::HudNotifyCustom(body) => stream.write(body),
+            UserMessage::VoteStart(body) => stream.write(body),
+            UserMessage::VotePass(body) => stream.write(body),
+            UserMessage::VoteFailed(body) => stream.write(body),
+            UserMessage::CallVoteFailed(body) => stream.write(body),
+            UserMessage::VoteSetup(body) => stream.write(body),
             UserMessage::Unknown(body) => stream.write(&body.data),
         })?;
 
@@ -454,6 +546,678 @@ pub struct HapMeleeContactMessage {
     pub data: u8,
 }
 
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HudTextMessage {
+    pub text: MaybeUtf8String,
+}
+
+/// Text shown on screen with position, colors and fade effects
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HudMsgMessage {
+    pub channel: u8,
+    pub x: f32,
+    pub y: f32,
+    pub color1: [u8; 4],
+    pub color2: [u8; 4],
+    pub effect: u8,
+    pub fade_in_time: f32,
+    pub fade_out_time: f32,
+    pub hold_time: f32,
+    pub fx_time: f32,
+    pub text: MaybeUtf8String,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HintTextMessage {
+    pub text: MaybeUtf8String,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyHintTextMessage {
+    #[size_bits = 8]
+    pub messages: Vec<MaybeUtf8String>,
+}
+
+impl<E: Endianness> BitWrite<E> for KeyHintTextMessage {
+    fn write(&self, stream: &mut BitWriteStream<E>) -> ReadResult<()> {
+        (self.messages.len() as u8).write(stream)?;
+        for message in &self.messages {
+            message.write(stream)?;
+        }
+        Ok(())
+    }
+}
+
+/// Damage taken by the local player
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamageMessage {
+    pub damage: u16,
+    pub damage_type: u32,
+    /// Origin of the damage, only set when a damage indicator is shown
+    pub origin: Option<Vector>,
+}
+
+impl BitRead<'_, LittleEndian> for DamageMessage {
+    fn read(stream: &mut Stream) -> ReadResult<Self> {
+        let damage = stream.read()?;
+        let damage_type = stream.read()?;
+        let origin = if stream.read()? {
+            Some(read_bit_vec3_coord(stream)?)
+        } else {
+            None
+        };
+        Ok(DamageMessage {
+            damage,
+            damage_type,
+            origin,
+        })
+    }
+}
+
+impl BitWrite<LittleEndian> for DamageMessage {
+    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+        self.damage.write(stream)?;
+        self.damage_type.write(stream)?;
+        self.origin.is_some().write(stream)?;
+        if let Some(origin) = self.origin {
+            write_bit_vec3_coord(origin, stream)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerIgnitedMessage {
+    #[size = 8]
+    pub pyro: EntityId,
+    #[size = 8]
+    pub victim: EntityId,
+    pub weapon_id: u8,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerJaratedMessage {
+    #[size = 8]
+    pub thrower: EntityId,
+    #[size = 8]
+    pub victim: EntityId,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerExtinguishedMessage {
+    #[size = 8]
+    pub healer: EntityId,
+    #[size = 8]
+    pub victim: EntityId,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerShieldBlockedMessage {
+    #[size = 8]
+    pub attacker: EntityId,
+    #[size = 8]
+    pub blocker: EntityId,
+}
+
+/// Gibs of a destroyed building
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreakModelMessage {
+    pub model_index: u16,
+    pub origin: Vector,
+    pub angles: Vector,
+    pub skin: u16,
+}
+
+impl BitRead<'_, LittleEndian> for BreakModelMessage {
+    fn read(stream: &mut Stream) -> ReadResult<Self> {
+        Ok(BreakModelMessage {
+            model_index: stream.read()?,
+            origin: read_bit_vec3_coord(stream)?,
+            angles: read_bit_vec3_coord(stream)?,
+            skin: stream.read()?,
+        })
+    }
+}
+
+impl BitWrite<LittleEndian> for BreakModelMessage {
+    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+        self.model_index.write(stream)?;
+        write_bit_vec3_coord(self.origin, stream)?;
+        write_bit_vec3_coord(self.angles, stream)?;
+        self.skin.write(stream)
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerBonusPointsMessage {
+    pub points: u8,
+    #[size = 8]
+    pub player: EntityId,
+    // the entity that awarded the points
+    #[size = 16]
+    pub source: EntityId,
+}
+
+/// Damage avoided by a scout using Bonk
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamageDodgedMessage {
+    pub damage: u16,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AchievementEventMessage {
+    pub achievement: u16,
+    pub count: u16,
+}
+
+/// The stats of the local player for the current life
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatsUpdateMessage {
+    pub class: u8,
+    pub alive: bool,
+    /// The stats that were updated, as stat type and value
+    pub stats: Vec<(u8, i32)>,
+}
+
+impl PlayerStatsUpdateMessage {
+    pub fn get(&self, stat: u8) -> Option<i32> {
+        self.stats
+            .iter()
+            .find_map(|(ty, value)| (*ty == stat).then_some(*value))
+    }
+}
+
+impl BitRead<'_, LittleEndian> for PlayerStatsUpdateMessage {
+    fn read(stream: &mut Stream) -> ReadResult<Self> {
+        let class = stream.read()?;
+        let alive = stream.read::<u8>()? != 0;
+        let mask: u32 = stream.read()?;
+        let mut stats = Vec::with_capacity(mask.count_ones() as usize);
+        // stat types start at 1
+        for bit in 0..32u8 {
+            if mask & (1 << bit) != 0 {
+                stats.push((bit + 1, stream.read()?));
+            }
+        }
+        Ok(PlayerStatsUpdateMessage {
+            class,
+            alive,
+            stats,
+        })
+    }
+}
+
+impl BitWrite<LittleEndian> for PlayerStatsUpdateMessage {
+    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+        self.class.write(stream)?;
+        (self.alive as u8).write(stream)?;
+        let mask = self
+            .stats
+            .iter()
+            .filter(|(ty, _)| (1..=32).contains(ty))
+            .fold(0u32, |mask, (ty, _)| mask | 1 << (ty - 1));
+        mask.write(stream)?;
+        for bit in 0..32u8 {
+            if mask & (1 << bit) != 0 {
+                self.get(bit + 1).unwrap_or_default().write(stream)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HudNotifyCustomMessage {
+    pub text: MaybeUtf8String,
+    pub icon: MaybeUtf8String,
+    // team color to use for the background
+    pub team: u8,
+}
+
+/// A vote was called, sent to every player that can vote on it
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteStartMessage {
+    // the team that can vote, 255 if everyone can vote
+    pub team: u8,
+    pub vote_index: u32,
+    // the player that called the vote, 99 if the vote was started by the server
+    #[size = 8]
+    pub initiator: EntityId,
+    pub issue: MaybeUtf8String,
+    pub details: MaybeUtf8String,
+    pub yes_no: bool,
+    // the player targeted by the vote, 0 if the issue doesn't target a player
+    #[size = 8]
+    pub target: EntityId,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VotePassMessage {
+    pub team: u8,
+    pub vote_index: u32,
+    pub issue: MaybeUtf8String,
+    pub details: MaybeUtf8String,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteFailedMessage {
+    pub team: u8,
+    pub vote_index: u32,
+    pub reason: u8,
+}
+
+/// A vote couldn't be called, only sent to the player calling the vote
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallVoteFailedMessage {
+    pub reason: u8,
+    // seconds until a vote can be called again, for cooldown failures
+    pub time: u16,
+}
+
+/// The vote issues available on the server
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteSetupMessage {
+    #[size_bits = 8]
+    pub issues: Vec<VoteIssue>,
+}
+
+impl<E: Endianness> BitWrite<E> for VoteSetupMessage {
+    fn write(&self, stream: &mut BitWriteStream<E>) -> ReadResult<()> {
+        (self.issues.len() as u8).write(stream)?;
+        for issue in &self.issues {
+            issue.write(stream)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(BitRead, BitWrite, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteIssue {
+    pub name: MaybeUtf8String,
+    pub translation: MaybeUtf8String,
+    pub enabled: u8,
+}
+
+#[cfg(test)]
+fn read_user_message_body<'a, T: BitRead<'a, LittleEndian>>(bytes: &'a [u8]) -> T {
+    use bitbuffer::BitReadBuffer;
+
+    let mut stream = Stream::from(BitReadBuffer::new(bytes, LittleEndian));
+    stream.read().unwrap()
+}
+
+#[cfg(test)]
+fn read_user_message(bytes: &[u8]) -> UserMessage<'_> {
+    use bitbuffer::BitReadBuffer;
+
+    let mut stream = Stream::from(BitReadBuffer::new(bytes, LittleEndian));
+    stream.read().unwrap()
+}
+
+/// Check that the message encodes back into the bytes it was read from
+#[cfg(test)]
+fn assert_user_message_bytes(bytes: &[u8], message: &UserMessage) {
+    let mut data = Vec::new();
+    {
+        let mut stream = BitWriteStream::new(&mut data, LittleEndian);
+        message.write(&mut stream).unwrap();
+    }
+    assert_eq!(bytes, data.as_slice());
+}
+
+/// Check that a body read as user message of the given type encodes back into the same bytes,
+/// including the type and length written by the server
+#[cfg(test)]
+fn assert_user_message_body(message_type: UserMessageType, body: &[u8]) {
+    let mut bytes = Vec::new();
+    {
+        let mut stream = BitWriteStream::new(&mut bytes, LittleEndian);
+        message_type.write(&mut stream).unwrap();
+        stream.write_int(body.len() * 8, 11).unwrap();
+        stream.write_bytes(body).unwrap();
+    }
+    let message = read_user_message(&bytes);
+    assert_eq!(message_type as u8, message.message_type());
+    assert_user_message_bytes(&bytes, &message);
+}
+
+#[test]
+fn test_demo_user_messages() {
+    // from small.dem
+    let bytes = [
+        0x05, 0xb0, 0x18, 0x18, 0xa1, 0x32, 0xfa, 0xa2, 0x4b, 0x6b, 0x2b, 0x63, 0x2b, 0x33, 0xa3,
+        0x03, 0x90, 0xc9, 0x01, 0xa8, 0xa9, 0x01, 0x00, 0x00, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::Text(Box::new(TextMessage {
+            location: HudTextLocation::PrintTalk,
+            text: "#TF_timeleft".into(),
+            substitute: ["29".into(), "55".into(), "".into(), "".into()],
+        })),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+
+    // from small.dem and short-2024.dem
+    let bytes = [0x01, 0x08, 0x00, 0x00];
+    let message = read_user_message(&bytes);
+    assert_eq!(UserMessage::Train(TrainMessage { data: 0 }), message);
+    assert_user_message_bytes(&bytes, &message);
+
+    let bytes = [0x06, 0x08, 0x00, 0x00];
+    let message = read_user_message(&bytes);
+    assert_eq!(UserMessage::ResetHUD(ResetHudMessage { data: 0 }), message);
+    assert_user_message_bytes(&bytes, &message);
+}
+
+#[test]
+fn test_gameplay_user_messages() {
+    let body = [3, 7, 21];
+    let ignited: PlayerIgnitedMessage = read_user_message_body(&body);
+    assert_eq!(
+        PlayerIgnitedMessage {
+            pyro: 3u32.into(),
+            victim: 7u32.into(),
+            weapon_id: 21,
+        },
+        ignited
+    );
+    crate::test_roundtrip_write(ignited);
+    assert_user_message_body(UserMessageType::PlayerIgnited, &body);
+
+    let body = [12, 4];
+    let jarated: PlayerJaratedMessage = read_user_message_body(&body);
+    assert_eq!(
+        PlayerJaratedMessage {
+            thrower: 12u32.into(),
+            victim: 4u32.into(),
+        },
+        jarated
+    );
+    crate::test_roundtrip_write(jarated);
+    assert_user_message_body(UserMessageType::PlayerJarated, &body);
+
+    let body = [5, 9];
+    let extinguished: PlayerExtinguishedMessage = read_user_message_body(&body);
+    assert_eq!(EntityId::from(5u32), extinguished.healer);
+    crate::test_roundtrip_write(extinguished);
+    assert_user_message_body(UserMessageType::PlayerExtinguished, &body);
+
+    let body = [2, 11];
+    let blocked: PlayerShieldBlockedMessage = read_user_message_body(&body);
+    assert_eq!(EntityId::from(11u32), blocked.blocker);
+    crate::test_roundtrip_write(blocked);
+    assert_user_message_body(UserMessageType::PlayerShieldBlocked, &body);
+
+    let body = [1, 6, 0x2c, 0x01];
+    let bonus: PlayerBonusPointsMessage = read_user_message_body(&body);
+    assert_eq!(
+        PlayerBonusPointsMessage {
+            points: 1,
+            player: 6u32.into(),
+            source: 300u32.into(),
+        },
+        bonus
+    );
+    crate::test_roundtrip_write(bonus);
+    assert_user_message_body(UserMessageType::PlayerBonusPoints, &body);
+
+    let body = [0x96, 0];
+    let dodged: DamageDodgedMessage = read_user_message_body(&body);
+    assert_eq!(150, dodged.damage);
+    crate::test_roundtrip_write(dodged);
+    assert_user_message_body(UserMessageType::DamageDodged, &body);
+
+    let body = [0xb6, 0x07, 1, 0];
+    let achievement: AchievementEventMessage = read_user_message_body(&body);
+    assert_eq!(
+        AchievementEventMessage {
+            achievement: 1974,
+            count: 1,
+        },
+        achievement
+    );
+    crate::test_roundtrip_write(achievement);
+    assert_user_message_body(UserMessageType::AchievementEvent, &body);
+
+    // damage from an unknown direction, the indicator flag isn't set
+    let body = [0x2d, 0, 0x02, 0x10, 0, 0, 0];
+    let damage: DamageMessage = read_user_message_body(&body);
+    assert_eq!(
+        DamageMessage {
+            damage: 45,
+            damage_type: 0x1002,
+            origin: None,
+        },
+        damage
+    );
+    crate::test_roundtrip_write(damage);
+
+    // blast damage with the indicator pointing at the origin of the explosion
+    let bytes = [
+        0x12, 0x5b, 0xd0, 0x02, 0x00, 0x02, 0x00, 0x00, 0xd8, 0xff, 0x0f, 0x30, 0x7f, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::Damage(DamageMessage {
+            damage: 90,
+            damage_type: 0x40,
+            origin: Some(Vector {
+                x: -1024.5,
+                y: 0.0,
+                z: 128.0,
+            }),
+        }),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+
+    let bytes = [
+        0x28, 0x6f, 0xe0, 0x0c, 0x78, 0xfe, 0x83, 0xff, 0x03, 0x28, 0x3f, 0x80, 0x92, 0x05, 0x04,
+        0x00, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::BreakModel(BreakModelMessage {
+            model_index: 412,
+            origin: Vector {
+                x: 512.0,
+                y: -256.25,
+                z: 64.0,
+            },
+            angles: Vector {
+                x: 0.0,
+                y: 90.0,
+                z: 0.0,
+            },
+            skin: 1,
+        }),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+}
+
+#[test]
+fn test_stats_user_message() {
+    let bytes = [
+        3, 1, // soldier, alive
+        0x05, 0, 0, 0, // shots hit and kills
+        4, 0, 0, 0, 2, 0, 0, 0,
+    ];
+    let stats: PlayerStatsUpdateMessage = read_user_message_body(&bytes);
+    assert_user_message_body(UserMessageType::PlayerStatsUpdate, &bytes);
+    assert_eq!(
+        PlayerStatsUpdateMessage {
+            class: 3,
+            alive: true,
+            stats: vec![(1, 4), (3, 2)],
+        },
+        stats
+    );
+    assert_eq!(Some(2), stats.get(3));
+    assert_eq!(None, stats.get(2));
+    crate::test_roundtrip_write(stats);
+}
+
+#[test]
+fn test_text_user_messages() {
+    let body = b"Setup ends in 10\0ico_notify_ten_seconds\0\x02";
+    let notify: HudNotifyCustomMessage = read_user_message_body(body);
+    assert_user_message_body(UserMessageType::HudNotifyCustom, body);
+    assert_eq!(
+        HudNotifyCustomMessage {
+            text: "Setup ends in 10".into(),
+            icon: "ico_notify_ten_seconds".into(),
+            team: 2,
+        },
+        notify
+    );
+    crate::test_roundtrip_write(notify);
+
+    let body = b"\x01#TF_Hint\0";
+    let hint: KeyHintTextMessage = read_user_message_body(body);
+    assert_user_message_body(UserMessageType::KeyHintText, body);
+    assert_eq!(vec![MaybeUtf8String::from("#TF_Hint")], hint.messages);
+    crate::test_roundtrip_write(hint);
+
+    let bytes = [
+        0x02, 0x88, 0x18, 0xa1, 0x32, 0xfa, 0x9a, 0x82, 0xcb, 0xfb, 0x22, 0x4a, 0x9b, 0x3b, 0xab,
+        0x4b, 0x9b, 0x2b, 0x03, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::HudText(HudTextMessage {
+            text: "#TF_Spy_Disguise".into(),
+        }),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+
+    let bytes = [
+        0x13, 0xb8, 0x18, 0x41, 0x4a, 0x73, 0xa3, 0xfb, 0x9a, 0x83, 0x7b, 0xa3, 0xa3, 0x2b, 0x23,
+        0xfb, 0x0a, 0xfb, 0x32, 0x93, 0x4b, 0x2b, 0x73, 0x23, 0x03, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::HintText(HintTextMessage {
+            text: "#Hint_spotted_a_friend".into(),
+        }),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+
+    // game_text on channel 1
+    let bytes = [
+        0x15, 0x50, 0x09, 0x00, 0x00, 0x00, 0xfc, 0x05, 0x00, 0x00, 0xf4, 0xf9, 0xff, 0xff, 0xff,
+        0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x01, 0x00, 0x00, 0xf8, 0x01, 0x00,
+        0x00, 0x05, 0x02, 0x00, 0x00, 0x00, 0xb8, 0x2a, 0x63, 0x1b, 0x7b, 0x6b, 0x2b, 0x03, 0x00,
+    ];
+    let message = read_user_message(&bytes);
+    assert_eq!(
+        UserMessage::HudMsg(Box::new(HudMsgMessage {
+            channel: 1,
+            x: -1.0,
+            y: 0.25,
+            color1: [255, 255, 255, 255],
+            color2: [0, 0, 0, 0],
+            effect: 0,
+            fade_in_time: 0.125,
+            fade_out_time: 0.5,
+            hold_time: 5.0,
+            fx_time: 0.0,
+            text: "Welcome".into(),
+        })),
+        message
+    );
+    assert_user_message_bytes(&bytes, &message);
+}
+
+#[test]
+fn test_vote_user_messages() {
+    let mut bytes = vec![2, 7, 0, 0, 0, 3];
+    bytes.extend_from_slice(b"#TF_vote_kick_player_other\0Player\0");
+    // yes/no flag followed by the target, not byte aligned
+    bytes.extend_from_slice(&[0x0b, 0x00]);
+    let start: VoteStartMessage = read_user_message_body(&bytes);
+    assert_eq!(
+        VoteStartMessage {
+            team: 2,
+            vote_index: 7,
+            initiator: 3u32.into(),
+            issue: "#TF_vote_kick_player_other".into(),
+            details: "Player".into(),
+            yes_no: true,
+            target: 5u32.into(),
+        },
+        start
+    );
+    crate::test_roundtrip_write(start);
+
+    let failed: VoteFailedMessage = read_user_message_body(&[255, 7, 0, 0, 0, 3]);
+    assert_eq!(
+        VoteFailedMessage {
+            team: 255,
+            vote_index: 7,
+            reason: 3,
+        },
+        failed
+    );
+    crate::test_roundtrip_write(failed);
+
+    let call_failed: CallVoteFailedMessage = read_user_message_body(&[8, 0x2c, 0x01]);
+    assert_eq!(300, call_failed.time);
+    crate::test_roundtrip_write(call_failed);
+
+    let mut bytes = vec![3, 8, 0, 0, 0];
+    bytes.extend_from_slice(b"#TF_vote_passed_changelevel\0cp_process_final\0");
+    let pass: VotePassMessage = read_user_message_body(&bytes);
+    assert_eq!(
+        VotePassMessage {
+            team: 3,
+            vote_index: 8,
+            issue: "#TF_vote_passed_changelevel".into(),
+            details: "cp_process_final".into(),
+        },
+        pass
+    );
+    crate::test_roundtrip_write(pass);
+
+    let setup: VoteSetupMessage =
+        read_user_message_body(b"\x02Kick\0#TF_Kick\0\x01ChangeLevel\0#TF_ChangeLevel\0\x00");
+    assert_eq!(2, setup.issues.len());
+    assert_eq!(
+        VoteIssue {
+            name: "ChangeLevel".into(),
+            translation: "#TF_ChangeLevel".into(),
+            enabled: 0,
+        },
+        setup.issues[1]
+    );
+    crate::test_roundtrip_write(setup);
+}
+
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(bound(deserialize = "'a: 'static"))]
@@ -1,14 +1,21 @@
-use bitbuffer::{BitRead, BitWrite, BitWriteSized, BitWriteStream, LittleEndian};
+use bitbuffer::{
+    BitError, BitRead, BitReadBuffer, BitReadSized, BitWrite, BitWriteSized, BitWriteStream,
+    LittleEndian,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{ReadResult, Stream};
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::parser::{Encode, ParseBitSkip};
+use crate::demo::vector::Vector;
+use crate::{Parse, ParseError, ParserState, ReadResult, Result, Stream};
 
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VoiceInitMessage {
-    codec: String,
-    quality: u8,
-    sampling_rate: u16,
+    /// The voice codec used by the server, `"steam"` for Steam voice
+    pub codec: String,
+    pub quality: u8,
+    pub sampling_rate: u16,
 }
 
 impl BitRead<'_, LittleEndian> for VoiceInitMessage {
@@ -67,25 +74,420 @@ fn test_voice_init_roundtrip() {
 #[endianness = "LittleEndian"]
 #[serde(bound(deserialize = "'a: 'static"))]
 pub struct VoiceDataMessage<'a> {
-    client: u8,
-    proximity: u8,
-    length: u16,
+    // the index of the speaking client, the entity id of the player is one higher
+    pub client: u8,
+    pub proximity: u8,
+    // length of the voice data in bits
+    pub length: u16,
     #[size = "length"]
-    data: Stream<'a>,
+    pub data: Stream<'a>,
+}
+
+impl VoiceDataMessage<'_> {
+    /// The entity id of the speaking player
+    pub fn entity_id(&self) -> EntityId {
+        EntityId::from(self.client as u32 + 1)
+    }
 }
 
+const VOICE_PAYLOAD_SILENCE: u8 = 0;
+const VOICE_PAYLOAD_SILK: u8 = 4;
+const VOICE_PAYLOAD_OPUS_PLC: u8 = 6;
+const VOICE_PAYLOAD_SAMPLE_RATE: u8 = 11;
+/// Marks an opus decoder reset instead of a frame length
+const OPUS_FRAME_RESET: u16 = 0xFFFF;
+
+/// Voice packet as sent by the Steam voice api, used when the voice codec is `"steam"`
+///
+/// A packet contains the steam id of the speaker followed by a number of payloads and a crc32
+/// checksum.
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(bound(deserialize = "'a: 'static"))]
-pub struct ParseSoundsMessage<'a> {
+pub struct SteamVoicePacket {
+    pub steam_id: u64,
+    pub payloads: Vec<VoicePayload>,
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VoicePayload {
+    /// Sample rate of the following audio
+    SampleRate(u16),
+    /// A number of silent samples
+    Silence(u16),
+    Silk(Vec<u8>),
+    Opus(Vec<OpusFrame>),
+}
+
+/// Opus packet with sequence number for packet loss concealment
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpusFrame {
+    pub sequence: u16,
+    pub data: Vec<u8>,
+}
+
+impl SteamVoicePacket {
+    pub fn parse(data: &[u8]) -> ReadResult<Self> {
+        // strip the trailing checksum
+        let data = data.get(..data.len().saturating_sub(4)).unwrap_or_default();
+        let mut stream = Stream::from(BitReadBuffer::new(data, LittleEndian));
+        let steam_id = stream.read()?;
+        let mut payloads = Vec::new();
+        while stream.bits_left() >= 8 {
+            let ty: u8 = stream.read()?;
+            let payload = match ty {
+                VOICE_PAYLOAD_SILENCE => VoicePayload::Silence(stream.read()?),
+                VOICE_PAYLOAD_SAMPLE_RATE => VoicePayload::SampleRate(stream.read()?),
+                VOICE_PAYLOAD_SILK => {
+                    let length: u16 = stream.read()?;
+                    VoicePayload::Silk(stream.read_bytes(length as usize)?.into_owned())
+                }
+                VOICE_PAYLOAD_OPUS_PLC => {
+                    let length: u16 = stream.read()?;
+                    let mut data = stream.read_bits(length as usize * 8)?;
+                    let mut frames = Vec::new();
+                    while data.bits_left() >= 16 {
+                        let length: u16 = data.read()?;
+                        if length == OPUS_FRAME_RESET {
+                            continue;
+                        }
+                        let sequence = data.read()?;
+                        let frame = data.read_bytes(length as usize)?.into_owned();
+                        frames.push(OpusFrame {
+                            sequence,
+                            data: frame,
+                        });
+                    }
+                    VoicePayload::Opus(frames)
+                }
+                _ => {
+                    return Err(BitError::UnmatchedDiscriminant {
+                        discriminant: ty as usize,
+                        enum_name: "VoicePayload".to_string(),
+                    })
+                }
+            };
+            payloads.push(payload);
+        }
+        Ok(SteamVoicePacket { steam_id, payloads })
+    }
+
+    pub fn sample_rate(&self) -> Option<u16> {
+        self.payloads.iter().find_map(|payload| match payload {
+            VoicePayload::SampleRate(rate) => Some(*rate),
+            _ => None,
+        })
+    }
+}
+
+#[test]
+fn test_parse_steam_voice_packet() {
+    let mut data = 76561198000000000u64.to_le_bytes().to_vec();
+    data.extend_from_slice(&[VOICE_PAYLOAD_SAMPLE_RATE, 0xc0, 0x5d]);
+    data.extend_from_slice(&[VOICE_PAYLOAD_OPUS_PLC, 15, 0]);
+    data.extend_from_slice(&[3, 0, 1, 0, 0x48, 1, 2]);
+    data.extend_from_slice(&[0xff, 0xff]);
+    data.extend_from_slice(&[2, 0, 2, 0, 0x48, 3]);
+    data.extend_from_slice(&[VOICE_PAYLOAD_SILENCE, 0xe0, 0x01]);
+    // checksum
+    data.extend_from_slice(&[0; 4]);
+
+    let packet = SteamVoicePacket::parse(&data).unwrap();
+    assert_eq!(76561198000000000, packet.steam_id);
+    assert_eq!(Some(24000), packet.sample_rate());
+    assert_eq!(
+        vec![
+            VoicePayload::SampleRate(24000),
+            VoicePayload::Opus(vec![
+                OpusFrame {
+                    sequence: 1,
+                    data: vec![0x48, 1, 2],
+                },
+                OpusFrame {
+                    sequence: 2,
+                    data: vec![0x48, 3],
+                },
+            ]),
+            VoicePayload::Silence(480),
+        ],
+        packet.payloads
+    );
+
+    let mut unknown = 76561198000000000u64.to_le_bytes().to_vec();
+    unknown.extend_from_slice(&[3, 0, 0, 0, 0]);
+    assert!(SteamVoicePacket::parse(&unknown).is_err());
+    assert!(SteamVoicePacket::parse(&[0; 4]).is_err());
+}
+
+/// Sound flag for sounds that stop a playing sound
+pub const SOUND_FLAG_STOP: u16 = 1 << 2;
+
+const SOUND_DELAY_OFFSET: f32 = 0.1;
+const DEFAULT_SOUND_CHANNEL: u8 = 6;
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseSoundsMessage {
     pub reliable: bool,
-    pub num: u8,
-    pub length: u16,
-    pub data: Stream<'a>,
+    pub sounds: Vec<SoundInfo>,
+}
+
+/// A single sound played by the server
+///
+/// Sounds are delta encoded against the previous sound in the message.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundInfo {
+    pub entity_index: EntityId,
+    /// Index into the `soundprecache` string table
+    pub sound_index: u16,
+    pub flags: u16,
+    pub channel: u8,
+    pub ambient: bool,
+    pub sentence: bool,
+    pub sequence_number: u16,
+    pub volume: f32,
+    pub sound_level: u16,
+    pub pitch: u8,
+    pub special_dsp: u8,
+    /// Delay in seconds
+    pub delay: f32,
+    pub origin: Vector,
+    pub speaker_entity: i16,
+}
+
+impl Default for SoundInfo {
+    fn default() -> Self {
+        SoundInfo {
+            entity_index: EntityId::default(),
+            sound_index: 0,
+            flags: 0,
+            channel: DEFAULT_SOUND_CHANNEL,
+            ambient: false,
+            sentence: false,
+            sequence_number: 0,
+            volume: 1.0,
+            sound_level: 75,
+            pitch: 100,
+            special_dsp: 0,
+            delay: 0.0,
+            origin: Vector::default(),
+            speaker_entity: -1,
+        }
+    }
+}
+
+/// Field sizes that changed between network protocol versions
+#[derive(Clone, Copy)]
+struct SoundEncoding {
+    sound_index_bits: usize,
+    flag_bits: usize,
+    special_dsp: bool,
 }
 
-impl<'a> BitRead<'a, LittleEndian> for ParseSoundsMessage<'a> {
-    fn read(stream: &mut Stream<'a>) -> ReadResult<Self> {
+impl SoundEncoding {
+    fn new(protocol: u32) -> Self {
+        SoundEncoding {
+            sound_index_bits: if protocol > 22 { 14 } else { 13 },
+            flag_bits: if protocol > 18 { 11 } else { 9 },
+            special_dsp: protocol > 21,
+        }
+    }
+}
+
+fn read_delta<'a, T: BitReadSized<'a, LittleEndian>>(
+    stream: &mut Stream<'a>,
+    bits: usize,
+    delta: T,
+) -> ReadResult<T> {
+    if stream.read()? {
+        stream.read_sized(bits)
+    } else {
+        Ok(delta)
+    }
+}
+
+fn write_delta<T: BitWriteSized<LittleEndian> + PartialEq>(
+    stream: &mut BitWriteStream<LittleEndian>,
+    bits: usize,
+    value: T,
+    delta: T,
+) -> ReadResult<()> {
+    if value == delta {
+        false.write(stream)
+    } else {
+        true.write(stream)?;
+        value.write_sized(stream, bits)
+    }
+}
+
+impl SoundInfo {
+    /// Whether this sound stops a playing sound
+    pub fn is_stop(&self) -> bool {
+        self.flags == SOUND_FLAG_STOP
+    }
+
+    /// Get the name of the sound from the `soundprecache` string table
+    pub fn sound_name<'s>(&self, state: &'s ParserState) -> Option<&'s str> {
+        state.strings.sound_name(self.sound_index as usize)
+    }
+
+    fn read_delta(
+        stream: &mut Stream,
+        delta: &SoundInfo,
+        encoding: SoundEncoding,
+    ) -> ReadResult<Self> {
+        let entity_index = if stream.read()? {
+            let bits = if stream.read()? { 5 } else { 11 };
+            EntityId::from(stream.read_sized::<u32>(bits)?)
+        } else {
+            delta.entity_index
+        };
+        let sound_index = read_delta(stream, encoding.sound_index_bits, delta.sound_index)?;
+        let flags = read_delta(stream, encoding.flag_bits, delta.flags)?;
+        let channel = read_delta(stream, 3, delta.channel)?;
+        let ambient = stream.read()?;
+        let sentence = stream.read()?;
+
+        let mut sound = SoundInfo {
+            entity_index,
+            sound_index,
+            flags,
+            channel,
+            ambient,
+            sentence,
+            ..SoundInfo::default()
+        };
+
+        if sound.is_stop() {
+            sound.volume = 0.0;
+            sound.sound_level = 0;
+            return Ok(sound);
+        }
+
+        sound.sequence_number = if stream.read()? {
+            delta.sequence_number
+        } else if stream.read()? {
+            delta.sequence_number.wrapping_add(1)
+        } else {
+            stream.read_sized(10)?
+        };
+        sound.volume = if stream.read()? {
+            stream.read_sized::<u8>(7)? as f32 / 127.0
+        } else {
+            delta.volume
+        };
+        sound.sound_level = read_delta(stream, 9, delta.sound_level)?;
+        sound.pitch = read_delta(stream, 8, delta.pitch)?;
+        if encoding.special_dsp {
+            sound.special_dsp = read_delta(stream, 8, delta.special_dsp)?;
+        }
+        sound.delay = if stream.read()? {
+            let mut delay = stream.read_sized::<i16>(13)? as f32 / 1000.0;
+            if delay < 0.0 {
+                delay *= 10.0;
+            }
+            delay - SOUND_DELAY_OFFSET
+        } else {
+            delta.delay
+        };
+        // origins are sent with a precision of 8 units
+        let mut read_coord = |delta: f32| -> ReadResult<f32> {
+            Ok(if stream.read()? {
+                stream.read_sized::<i16>(12)? as f32 * 8.0
+            } else {
+                delta
+            })
+        };
+        sound.origin = Vector {
+            x: read_coord(delta.origin.x)?,
+            y: read_coord(delta.origin.y)?,
+            z: read_coord(delta.origin.z)?,
+        };
+        sound.speaker_entity = read_delta(stream, 12, delta.speaker_entity)?;
+
+        Ok(sound)
+    }
+
+    fn write_delta(
+        &self,
+        stream: &mut BitWriteStream<LittleEndian>,
+        delta: &SoundInfo,
+        encoding: SoundEncoding,
+    ) -> ReadResult<()> {
+        if self.entity_index == delta.entity_index {
+            false.write(stream)?;
+        } else {
+            true.write(stream)?;
+            let index = u32::from(self.entity_index);
+            let short = index <= 31;
+            short.write(stream)?;
+            index.write_sized(stream, if short { 5 } else { 11 })?;
+        }
+        write_delta(
+            stream,
+            encoding.sound_index_bits,
+            self.sound_index,
+            delta.sound_index,
+        )?;
+        write_delta(stream, encoding.flag_bits, self.flags, delta.flags)?;
+        write_delta(stream, 3, self.channel, delta.channel)?;
+        self.ambient.write(stream)?;
+        self.sentence.write(stream)?;
+
+        if self.is_stop() {
+            return Ok(());
+        }
+
+        if self.sequence_number == delta.sequence_number {
+            true.write(stream)?;
+        } else if self.sequence_number == delta.sequence_number.wrapping_add(1) {
+            false.write(stream)?;
+            true.write(stream)?;
+        } else {
+            0u8.write_sized(stream, 2)?;
+            self.sequence_number.write_sized(stream, 10)?;
+        }
+        if self.volume == delta.volume {
+            false.write(stream)?;
+        } else {
+            true.write(stream)?;
+            ((self.volume * 127.0).round() as u8).write_sized(stream, 7)?;
+        }
+        write_delta(stream, 9, self.sound_level, delta.sound_level)?;
+        write_delta(stream, 8, self.pitch, delta.pitch)?;
+        if encoding.special_dsp {
+            write_delta(stream, 8, self.special_dsp, delta.special_dsp)?;
+        }
+        if self.delay == delta.delay {
+            false.write(stream)?;
+        } else {
+            true.write(stream)?;
+            let mut delay = (self.delay + SOUND_DELAY_OFFSET) * 1000.0;
+            if delay < 0.0 {
+                delay /= 10.0;
+            }
+            (delay.round() as i16).write_sized(stream, 13)?;
+        }
+        for (value, delta) in [
+            (self.origin.x, delta.origin.x),
+            (self.origin.y, delta.origin.y),
+            (self.origin.z, delta.origin.z),
+        ] {
+            write_delta(
+                stream,
+                12,
+                (value / 8.0).round() as i16,
+                (delta / 8.0).round() as i16,
+            )?;
+        }
+        write_delta(stream, 12, self.speaker_entity, delta.speaker_entity)
+    }
+}
+
+impl Parse<'_> for ParseSoundsMessage {
+    fn parse(stream: &mut Stream, state: &ParserState) -> Result<Self> {
         let reliable = stream.read()?;
         let num = if reliable { 1u8 } else { stream.read()? };
         let length = if reliable {
@@ -93,51 +495,189 @@ impl<'a> BitRead<'a, LittleEndian> for ParseSoundsMessage<'a> {
         } else {
             stream.read()?
         };
-        let data = stream.read_sized(length as usize)?;
+        let mut data = stream.read_bits(length as usize)?;
 
-        Ok(ParseSoundsMessage {
-            reliable,
-            num,
-            length,
-            data,
-        })
+        let encoding = SoundEncoding::new(state.protocol_version);
+        let mut sounds: Vec<SoundInfo> = Vec::with_capacity(num as usize);
+        let default = SoundInfo::default();
+        for _ in 0..num {
+            let delta = sounds.last().unwrap_or(&default);
+            let sound = SoundInfo::read_delta(&mut data, delta, encoding)?;
+            sounds.push(sound);
+        }
+
+        Ok(ParseSoundsMessage { reliable, sounds })
     }
 }
 
-impl<'a> BitWrite<LittleEndian> for ParseSoundsMessage<'a> {
-    fn write(&self, stream: &mut BitWriteStream<LittleEndian>) -> ReadResult<()> {
+impl Encode for ParseSoundsMessage {
+    fn encode(&self, stream: &mut BitWriteStream<LittleEndian>, state: &ParserState) -> Result<()> {
         self.reliable.write(stream)?;
         if !self.reliable {
-            self.num.write(stream)?;
+            (self.sounds.len() as u8).write(stream)?;
         }
 
-        if self.reliable {
-            self.length.write_sized(stream, 8)?;
-        } else {
-            self.length.write(stream)?;
-        }
-
-        self.data.write(stream)?;
+        let encoding = SoundEncoding::new(state.protocol_version);
+        let length_bits = if self.reliable { 8 } else { 16 };
+        stream.reserve_length(length_bits, |stream| {
+            let mut delta = &SoundInfo::default();
+            for sound in self.sounds.iter() {
+                sound.write_delta(stream, delta, encoding)?;
+                delta = sound;
+            }
+            Ok::<(), ParseError>(())
+        })
+    }
+}
 
-        Ok(())
+impl ParseBitSkip<'_> for ParseSoundsMessage {
+    fn parse_skip(stream: &mut Stream, _state: &ParserState) -> Result<()> {
+        let reliable: bool = stream.read()?;
+        let length = if reliable {
+            stream.read_sized::<u16>(8)?
+        } else {
+            stream.skip_bits(8)?;
+            stream.read()?
+        };
+        stream.skip_bits(length as usize).map_err(ParseError::from)
     }
 }
 
 #[test]
 fn test_parse_sounds_roundtrip() {
-    use bitbuffer::BitReadBuffer;
-    let inner = BitReadBuffer::new(&[1, 2, 3, 4, 5, 6], LittleEndian);
+    let state = ParserState::new(24, |_| false, false);
+    crate::test_roundtrip_encode(
+        ParseSoundsMessage {
+            reliable: false,
+            sounds: Vec::new(),
+        },
+        &state,
+    );
+    crate::test_roundtrip_encode(
+        ParseSoundsMessage {
+            reliable: true,
+            sounds: vec![SoundInfo {
+                entity_index: 445u32.into(),
+                sound_index: 6436,
+                flags: 11,
+                ambient: true,
+                origin: Vector {
+                    x: 0.0,
+                    y: 320.0,
+                    z: 1096.0,
+                },
+                ..SoundInfo::default()
+            }],
+        },
+        &state,
+    );
+    crate::test_roundtrip_encode(
+        ParseSoundsMessage {
+            reliable: false,
+            sounds: vec![
+                SoundInfo {
+                    entity_index: 12u32.into(),
+                    sound_index: 123,
+                    channel: 1,
+                    sequence_number: 5,
+                    volume: 64.0 / 127.0,
+                    sound_level: 90,
+                    pitch: 120,
+                    delay: -0.5 - SOUND_DELAY_OFFSET,
+                    origin: Vector {
+                        x: -1024.0,
+                        y: 64.0,
+                        z: -8.0,
+                    },
+                    ..SoundInfo::default()
+                },
+                SoundInfo {
+                    entity_index: 12u32.into(),
+                    sound_index: 123,
+                    channel: 1,
+                    sequence_number: 6,
+                    volume: 64.0 / 127.0,
+                    sound_level: 90,
+                    pitch: 120,
+                    delay: 0.25 - SOUND_DELAY_OFFSET,
+                    origin: Vector {
+                        x: -1024.0,
+                        y: 72.0,
+                        z: -8.0,
+                    },
+                    speaker_entity: 3,
+                    ..SoundInfo::default()
+                },
+                SoundInfo {
+                    entity_index: 1200u32.into(),
+                    sound_index: 5,
+                    flags: SOUND_FLAG_STOP,
+                    volume: 0.0,
+                    sound_level: 0,
+                    ..SoundInfo::default()
+                },
+                SoundInfo {
+                    entity_index: 1200u32.into(),
+                    sound_index: 6,
+                    sequence_number: 900,
+                    ..SoundInfo::default()
+                },
+            ],
+        },
+        &state,
+    );
+}
+
+#[test]
+fn test_sound_name() {
+    use crate::demo::data::stringtables::SOUND_PRECACHE;
+
+    let mut state = ParserState::new(24, |_| false, false);
+    state
+        .strings
+        .insert(SOUND_PRECACHE, 0, Some("Weapon_RPG.Single"), 0u32.into());
+    state.strings.insert(
+        SOUND_PRECACHE,
+        123,
+        Some("player/footsteps/concrete1.wav"),
+        0u32.into(),
+    );
 
-    crate::test_roundtrip_write(ParseSoundsMessage {
+    let sounds = ParseSoundsMessage {
         reliable: false,
-        num: 0,
-        length: inner.bit_len() as u16,
-        data: inner.clone().into(),
-    });
-    crate::test_roundtrip_write(ParseSoundsMessage {
-        reliable: true,
-        num: 1,
-        length: inner.bit_len() as u16,
-        data: inner.into(),
-    });
+        sounds: vec![
+            SoundInfo::default(),
+            SoundInfo {
+                sound_index: 123,
+                ..SoundInfo::default()
+            },
+            SoundInfo {
+                sound_index: 5,
+                ..SoundInfo::default()
+            },
+        ],
+    };
+    let mut data = Vec::new();
+    sounds
+        .encode(&mut BitWriteStream::new(&mut data, LittleEndian), &state)
+        .unwrap();
+    let decoded = ParseSoundsMessage::parse(
+        &mut Stream::from(BitReadBuffer::new(&data, LittleEndian)),
+        &state,
+    )
+    .unwrap();
+
+    let names: Vec<_> = decoded
+        .sounds
+        .iter()
+        .map(|sound| sound.sound_name(&state))
+        .collect();
+    assert_eq!(
+        vec![
+            Some("Weapon_RPG.Single"),
+            Some("player/footsteps/concrete1.wav"),
+            None
+        ],
+        names
+    );
 }
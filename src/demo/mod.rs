@@ -4,13 +4,21 @@ pub mod data;
 pub mod gameevent_gen;
 pub mod gamevent;
 pub mod header;
+pub mod keyvalues;
 pub mod lzss;
+pub mod merge;
 pub mod message;
+pub mod ogg;
 pub mod packet;
 pub mod parser;
+pub mod repair;
+pub mod rewrite;
 pub mod sendprop;
 mod sendprop_gen;
+pub mod strip;
+pub mod stv;
 pub mod vector;
+pub mod verify;
 
 pub type Buffer<'a> = BitReadBuffer<'a, LittleEndian>;
 pub type Stream<'a> = BitReadStream<'a, LittleEndian>;
@@ -0,0 +1,267 @@
+//! Minimal Ogg Opus writer
+//!
+//! Only supports writing a single logical stream with packets that fit in a single page, which is
+//! plenty for voice data.
+
+use std::io::{self, Write};
+
+const MAX_PAGE_SEGMENTS: usize = 255;
+/// Flush pages once they get this big, keeps the pages at a reasonable size for seeking
+const TARGET_PAGE_SIZE: usize = 4096;
+
+const HEADER_BOS: u8 = 0x02;
+const HEADER_EOS: u8 = 0x04;
+
+/// Ogg Opus granule positions are always at 48kHz
+pub const OPUS_SAMPLE_RATE: u32 = 48000;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        // can't use better method in const fns
+        #[allow(clippy::indexing_slicing)]
+        {
+            table[i] = crc;
+        }
+        i += 1;
+    }
+    table
+}
+
+/// The crc used by ogg pages, unlike the common crc32 this isn't bit reflected
+fn crc(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, byte| {
+        let index = ((crc >> 24) as u8 ^ byte) as usize;
+        (crc << 8) ^ CRC_TABLE.get(index).copied().unwrap_or_default()
+    })
+}
+
+/// Writes packets of a single logical stream into ogg pages
+pub struct OggWriter<W: Write> {
+    writer: W,
+    serial: u32,
+    sequence: u32,
+    granule: u64,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl<W: Write> OggWriter<W> {
+    pub fn new(writer: W, serial: u32) -> Self {
+        OggWriter {
+            writer,
+            serial,
+            sequence: 0,
+            granule: 0,
+            segments: Vec::with_capacity(MAX_PAGE_SEGMENTS),
+            data: Vec::with_capacity(TARGET_PAGE_SIZE),
+        }
+    }
+
+    /// Add a packet to the stream, `granule` is the granule position at the end of the packet
+    pub fn write_packet(&mut self, packet: &[u8], granule: u64) -> io::Result<()> {
+        let segment_count = packet.len() / 255 + 1;
+        if segment_count > MAX_PAGE_SEGMENTS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet too large for a single ogg page",
+            ));
+        }
+        if self.segments.len() + segment_count > MAX_PAGE_SEGMENTS {
+            self.write_page(0)?;
+        }
+
+        self.segments
+            .extend(std::iter::repeat(255).take(segment_count - 1));
+        self.segments.push((packet.len() % 255) as u8);
+        self.data.extend_from_slice(packet);
+        self.granule = granule;
+
+        if self.data.len() >= TARGET_PAGE_SIZE {
+            self.write_page(0)?;
+        }
+        Ok(())
+    }
+
+    /// Write all pending packets, the next packet will start on a new page
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.segments.is_empty() {
+            Ok(())
+        } else {
+            self.write_page(0)
+        }
+    }
+
+    /// Write the pending packets, marking the end of the stream
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_page(HEADER_EOS)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_page(&mut self, flags: u8) -> io::Result<()> {
+        let flags = if self.sequence == 0 {
+            flags | HEADER_BOS
+        } else {
+            flags
+        };
+
+        let mut page = Vec::with_capacity(27 + self.segments.len() + self.data.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0);
+        page.push(flags);
+        page.extend_from_slice(&self.granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        // crc is calculated with the crc field set to 0
+        page.extend_from_slice(&[0; 4]);
+        page.push(self.segments.len() as u8);
+        page.append(&mut self.segments);
+        page.append(&mut self.data);
+
+        let checksum = crc(&page);
+        if let Some(field) = page.get_mut(22..26) {
+            field.copy_from_slice(&checksum.to_le_bytes());
+        }
+
+        self.sequence += 1;
+        self.writer.write_all(&page)
+    }
+}
+
+/// Write the identification and comment headers for a mono opus stream
+pub fn write_opus_headers<W: Write>(
+    writer: &mut OggWriter<W>,
+    input_sample_rate: u32,
+) -> io::Result<()> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&0u16.to_le_bytes()); // pre-skip
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    writer.write_packet(&head, 0)?;
+    writer.flush()?;
+
+    let vendor = concat!("tf-demo-parser ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::with_capacity(16 + vendor.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes()); // user comments
+    writer.write_packet(&tags, 0)?;
+    writer.flush()
+}
+
+/// An opus packet containing a single 20ms frame without data, decoded as silence
+pub const OPUS_SILENCE_PACKET: [u8; 1] = [0xF8];
+pub const OPUS_SILENCE_SAMPLES: u32 = 960;
+
+/// Get the number of samples in an opus packet at 48kHz, based on the table of contents byte
+pub fn opus_packet_samples(packet: &[u8]) -> Option<u32> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+    let frame_size = match (config, config % 4) {
+        // silk, 10, 20, 40 or 60ms
+        (0..=11, 0) => 480,
+        (0..=11, 1) => 960,
+        (0..=11, 2) => 1920,
+        (0..=11, _) => 2880,
+        // hybrid, 10 or 20ms
+        (12..=15, 0 | 2) => 480,
+        (12..=15, _) => 960,
+        // celt, 2.5, 5, 10 or 20ms
+        (_, 0) => 120,
+        (_, 1) => 240,
+        (_, 2) => 480,
+        (_, _) => 960,
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3F) as u32,
+    };
+    Some(frame_size * frames)
+}
+
+#[test]
+fn test_opus_packet_samples() {
+    assert_eq!(None, opus_packet_samples(&[]));
+    assert_eq!(
+        Some(OPUS_SILENCE_SAMPLES),
+        opus_packet_samples(&OPUS_SILENCE_PACKET)
+    );
+    // silk wideband 20ms
+    assert_eq!(Some(960), opus_packet_samples(&[0x48, 0x00]));
+    // hybrid fullband 10ms, two frames
+    assert_eq!(Some(960), opus_packet_samples(&[0x71, 0x00]));
+    // celt 2.5ms, code 3 with 5 frames
+    assert_eq!(Some(600), opus_packet_samples(&[0x83, 0x05]));
+    assert_eq!(None, opus_packet_samples(&[0x83]));
+}
+
+#[test]
+fn test_ogg_pages() {
+    let mut writer = OggWriter::new(Vec::new(), 1234);
+    write_opus_headers(&mut writer, 24000).unwrap();
+    for i in 1..=300u64 {
+        writer.write_packet(&[0x48, i as u8], i * 960).unwrap();
+    }
+    let data = writer.finish().unwrap();
+
+    let mut pages = Vec::new();
+    let mut rest = data.as_slice();
+    while !rest.is_empty() {
+        assert_eq!(b"OggS", &rest[0..4]);
+        let segments = rest[26] as usize;
+        let length = 27
+            + segments
+            + rest[27..27 + segments]
+                .iter()
+                .map(|s| *s as usize)
+                .sum::<usize>();
+        let mut page = rest[..length].to_vec();
+        let checksum = u32::from_le_bytes(page[22..26].try_into().unwrap());
+        page[22..26].copy_from_slice(&[0; 4]);
+        assert_eq!(crc(&page), checksum);
+        pages.push(page);
+        rest = &rest[length..];
+    }
+
+    // 2 header pages, 300 packets don't fit in a single page
+    assert_eq!(4, pages.len());
+    assert_eq!(HEADER_BOS, pages[0][5]);
+    assert_eq!(b"OpusHead", &pages[0][28..36]);
+    assert_eq!(b"OpusTags", &pages[1][28..36]);
+    assert_eq!(HEADER_EOS, pages[3][5]);
+    for (sequence, page) in pages.iter().enumerate() {
+        assert_eq!(
+            sequence as u32,
+            u32::from_le_bytes(page[18..22].try_into().unwrap())
+        );
+        assert_eq!(1234, u32::from_le_bytes(page[14..18].try_into().unwrap()));
+    }
+    assert_eq!(
+        255 * 960,
+        u64::from_le_bytes(pages[2][6..14].try_into().unwrap())
+    );
+    assert_eq!(
+        300 * 960,
+        u64::from_le_bytes(pages[3][6..14].try_into().unwrap())
+    );
+}
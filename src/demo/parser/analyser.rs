@@ -10,7 +10,7 @@ use crate::demo::message::{Message, MessageType};
 use crate::demo::packet::stringtable::StringTableEntry;
 use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
 use crate::demo::vector::Vector;
-use crate::{ParserState, ReadResult, Stream};
+use crate::{ParserState, ReadResult};
 use bitbuffer::{BitWrite, BitWriteStream, Endianness};
 use num_enum::TryFromPrimitive;
 use parse_display::{Display, FromStr};
@@ -437,12 +437,10 @@ impl MessageHandler for Analyser {
         entry: &StringTableEntry,
         _parser_state: &ParserState,
     ) {
-        if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+        if let Some((_, user_info)) =
+            crate::demo::data::UserInfo::from_string_entry(table, index, entry)
+        {
+            user_info.update_users(&mut self.state.users);
         }
     }
 
@@ -512,27 +510,6 @@ impl Analyser {
             _ => {}
         }
     }
-
-    fn parse_user_info(
-        &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            self.state
-                .users
-                .entry(user_info.player_info.user_id)
-                .and_modify(|info| {
-                    info.entity_id = user_info.entity_id;
-                })
-                .or_insert_with(|| user_info.into());
-        }
-
-        Ok(())
-    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
@@ -0,0 +1,254 @@
+use crate::demo::data::DemoTick;
+use crate::demo::message::bspdecal::BSPDecalMessage;
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::message::tempentities::{PlayerDecalEvent, TempEntity};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::{UserId, UserInfo};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::vector::Vector;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Collects all decals placed in the map and the sprays of players
+#[derive(Default, Debug)]
+pub struct DecalAnalyser {
+    state: DecalState,
+    // crc of the spray file for every user
+    spray_files: HashMap<UserId, u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DecalState {
+    pub decals: Vec<Decal>,
+    pub sprays: Vec<Spray>,
+    pub users: BTreeMap<UserId, UserInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Decal {
+    pub tick: DemoTick,
+    pub position: Vector,
+    pub texture_index: u16,
+    /// Texture name from the `decalprecache` string table
+    pub texture: Option<String>,
+    /// The entity the decal is applied to, the world is entity 0
+    pub entity: EntityId,
+    pub model_index: u16,
+    /// Model name from the `modelprecache` string table
+    pub model: Option<String>,
+    pub low_priority: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Spray {
+    pub tick: DemoTick,
+    pub position: Vector,
+    /// The entity the spray is applied to, the world is entity 0
+    pub entity: EntityId,
+    pub player: EntityId,
+    pub user: Option<UserId>,
+    /// Crc of the custom spray file of the user
+    pub file_crc: Option<u32>,
+}
+
+impl Spray {
+    /// Path of the spray file in the game's download directory
+    pub fn file_name(&self) -> Option<String> {
+        let hex: String = self
+            .file_crc?
+            .to_le_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Some(format!("user_custom/{}/{}.dat", hex.get(0..2)?, hex))
+    }
+}
+
+impl MessageHandler for DecalAnalyser {
+    type Output = DecalState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::BspDecal | MessageType::TempEntities
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        match message {
+            Message::BspDecal(message) => self.handle_decal(message, tick, parser_state),
+            Message::TempEntities(message) => {
+                for event in &message.events {
+                    if let Some(TempEntity::PlayerDecal(spray)) = event.decode(parser_state) {
+                        self.handle_spray(&spray, tick);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        _parser_state: &ParserState,
+    ) {
+        if let Some((user_id, user_info)) =
+            crate::demo::data::UserInfo::from_string_entry(table, index, entry)
+        {
+            match user_info.player_info.custom_file[0] {
+                0 => self.spray_files.remove(&user_id),
+                crc => self.spray_files.insert(user_id, crc),
+            };
+            user_info.update_users(&mut self.state.users);
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for DecalAnalyser {
+    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+        &self.state
+    }
+}
+
+impl DecalAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_decal(&mut self, message: &BSPDecalMessage, tick: DemoTick, state: &ParserState) {
+        let strings = &state.strings;
+        self.state.decals.push(Decal {
+            tick,
+            position: message.position,
+            texture_index: message.texture_index,
+            texture: strings
+                .decal_name(message.texture_index as usize)
+                .map(String::from),
+            entity: EntityId::from(message.ent_index as u32),
+            model_index: message.model_index,
+            // model index 0 means the world
+            model: match message.model_index {
+                0 => None,
+                index => strings.model_name(index as usize).map(String::from),
+            },
+            low_priority: message.low_priority,
+        });
+    }
+
+    fn handle_spray(&mut self, spray: &PlayerDecalEvent, tick: DemoTick) {
+        let user = self
+            .state
+            .users
+            .values()
+            .find(|user| user.entity_id == spray.player)
+            .map(|user| user.user_id);
+        self.state.sprays.push(Spray {
+            tick,
+            position: spray.origin,
+            entity: spray.entity,
+            player: spray.player,
+            user,
+            file_crc: user.and_then(|user| self.spray_files.get(&user).copied()),
+        });
+    }
+}
+
+#[test]
+fn test_decal_log() {
+    use crate::demo::data::stringtables::{DECAL_PRECACHE, MODEL_PRECACHE};
+
+    let mut state = ParserState::new(24, |_| false, false);
+    let tick = DemoTick::default();
+    state
+        .strings
+        .insert(DECAL_PRECACHE, 3, Some("decals/scorch1"), tick);
+    state
+        .strings
+        .insert(MODEL_PRECACHE, 1, Some("maps/cp_process_final.bsp"), tick);
+    state.strings.insert(MODEL_PRECACHE, 12, Some("*12"), tick);
+
+    let mut analyser = DecalAnalyser::new();
+
+    let user_id = UserId::from(14u16);
+    let user = crate::demo::data::UserInfo {
+        entity_id: 2u32.into(),
+        player_info: crate::demo::data::userinfo::PlayerInfo {
+            name: "sprayer".into(),
+            user_id,
+            ..Default::default()
+        },
+    };
+    analyser.state.users.insert(user_id, user.into());
+    analyser.spray_files.insert(user_id, 0xdeadbeef);
+
+    let position = Vector {
+        x: 10.0,
+        y: 20.0,
+        z: -30.0,
+    };
+    analyser.handle_decal(
+        &BSPDecalMessage {
+            position,
+            texture_index: 3,
+            ent_index: 0,
+            model_index: 0,
+            low_priority: false,
+        },
+        DemoTick::from(5u32),
+        &state,
+    );
+    analyser.handle_decal(
+        &BSPDecalMessage {
+            position,
+            texture_index: 4,
+            ent_index: 40,
+            model_index: 12,
+            low_priority: true,
+        },
+        DemoTick::from(6u32),
+        &state,
+    );
+    analyser.handle_spray(
+        &PlayerDecalEvent {
+            player: 2u32.into(),
+            origin: position,
+            entity: 0u32.into(),
+        },
+        DemoTick::from(7u32),
+    );
+    analyser.handle_spray(
+        &PlayerDecalEvent {
+            player: 5u32.into(),
+            origin: position,
+            entity: 0u32.into(),
+        },
+        DemoTick::from(8u32),
+    );
+
+    let output = analyser.into_output(&state);
+    assert_eq!(Some("decals/scorch1"), output.decals[0].texture.as_deref());
+    assert_eq!(None, output.decals[0].model);
+    assert_eq!(None, output.decals[1].texture);
+    assert_eq!(Some("*12"), output.decals[1].model.as_deref());
+    assert_eq!(EntityId::from(40u32), output.decals[1].entity);
+
+    assert_eq!(Some(user_id), output.sprays[0].user);
+    assert_eq!(
+        Some("user_custom/ef/efbeadde.dat".to_string()),
+        output.sprays[0].file_name()
+    );
+    assert_eq!(None, output.sprays[1].user);
+    assert_eq!(None, output.sprays[1].file_name());
+}
@@ -1,14 +1,23 @@
 pub use crate::demo::data::game_state::{
     Building, BuildingClass, Dispenser, GameState, Kill, PlayerState, Sentry, Teleporter, World,
 };
-use crate::demo::data::game_state::{Handle, PipeType, Projectile, ProjectileType};
-use crate::demo::data::DemoTick;
-use crate::demo::gameevent_gen::ObjectDestroyedEvent;
+use crate::demo::data::game_state::{
+    CaptureEvent, CaptureEventKind, ConditionChange, Flag, FlagAction, FlagStatus, Handle, Medigun,
+    MedigunType, ObjectiveEvent, ObjectiveEventKind, PasstimeBall, PayloadCart, PayloadProgress,
+    PipeType, Projectile, ProjectileType, RoundTimer, RoundTimerState, RoundTimerUpdate, TeamState,
+    Train, Weapon, WeaponSlot, WeaponSwitch, MAX_CONTROL_POINTS,
+};
+use crate::demo::data::{DemoTick, MaybeUtf8String, ServerTick};
+use crate::demo::gameevent_gen::{
+    ObjectDestroyedEvent, PassBallBlockedEvent, PassBallStolenEvent, PassFreeEvent, PassGetEvent,
+    PassPassCaughtEvent, PassScoreEvent, TeamPlayCaptureBlockedEvent, TeamPlayFlagEventEvent,
+    TeamPlayPointCapturedEvent, TeamPlayPointStartCaptureEvent,
+};
 use crate::demo::gamevent::GameEvent;
 use crate::demo::message::gameevent::GameEventMessage;
 use crate::demo::message::packetentities::{EntityId, PacketEntity, UpdateType};
-use crate::demo::message::Message;
-use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::message::{Message, ServerInfoMessage};
+use crate::demo::packet::datatable::{ClassId, ParseSendTable, ServerClass, ServerClassName};
 use crate::demo::packet::message::MessagePacketMeta;
 use crate::demo::packet::stringtable::StringTableEntry;
 pub use crate::demo::parser::analyser::{Class, Team, UserId};
@@ -16,7 +25,8 @@ use crate::demo::parser::handler::BorrowMessageHandler;
 use crate::demo::parser::MessageHandler;
 use crate::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
 use crate::demo::vector::{Vector, VectorXY};
-use crate::{MessageType, ParserState, ReadResult, Stream};
+use crate::{MessageType, ParserState};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
@@ -26,7 +36,14 @@ pub struct CachedEntities {}
 pub struct GameStateAnalyser {
     pub state: GameState,
     tick: DemoTick,
+    server_tick: ServerTick,
     class_names: Vec<ServerClassName>, // indexed by ClassId
+    // handles of the red and blue koth timers
+    koth_timers: [Handle; 2],
+    // whether the entities of a class are weapons
+    weapon_classes: HashMap<ClassId, bool>,
+    // players that switched weapons during the current packet, resolved once all entities are updated
+    pending_switches: Vec<EntityId>,
 }
 
 impl MessageHandler for GameStateAnalyser {
@@ -35,7 +52,10 @@ impl MessageHandler for GameStateAnalyser {
     fn does_handle(message_type: MessageType) -> bool {
         matches!(
             message_type,
-            MessageType::PacketEntities | MessageType::GameEvent | MessageType::ServerInfo
+            MessageType::PacketEntities
+                | MessageType::GameEvent
+                | MessageType::ServerInfo
+                | MessageType::NetTick
         )
     }
 
@@ -45,14 +65,27 @@ impl MessageHandler for GameStateAnalyser {
                 for entity in &message.entities {
                     self.handle_entity(entity, parser_state);
                 }
+                self.resolve_weapon_switches();
                 for id in &message.removed_entities {
                     self.state.projectile_destroy(*id);
                     self.state.remove_building(*id);
+                    self.state.mediguns.remove(id);
+                    self.state.weapons.remove(id);
+                    self.state.payloads.remove(id);
+                    self.state.trains.remove(id);
+                    self.state.flags.remove(id);
+                    self.state.round_timers.remove(id);
+                    self.state.teams.retain(|team| team.entity != *id);
+                    self.state.entity_serials.remove(id);
+                    if self.state.ball.as_ref().map(|ball| ball.entity) == Some(*id) {
+                        self.state.ball = None;
+                    }
                 }
             }
             Message::ServerInfo(message) => {
                 self.state.interval_per_tick = message.interval_per_tick
             }
+            Message::NetTick(message) => self.server_tick = message.tick,
             Message::GameEvent(GameEventMessage { event, .. }) => match event {
                 GameEvent::PlayerDeath(death) => {
                     self.state.kills.push(Kill::new(self.tick, death.as_ref()))
@@ -62,10 +95,119 @@ impl MessageHandler for GameStateAnalyser {
                 }
                 GameEvent::TeamPlayRoundStart(_) => {
                     self.state.buildings.clear();
+                    self.state.round += 1;
                 }
                 GameEvent::ObjectDestroyed(ObjectDestroyedEvent { index, .. }) => {
                     self.state.remove_building((*index as u32).into());
                 }
+                GameEvent::TeamPlayPointStartCapture(TeamPlayPointStartCaptureEvent {
+                    cp,
+                    cp_name,
+                    team,
+                    cap_team,
+                    cappers,
+                    cap_time,
+                }) => self.log_capture_event(
+                    *cp,
+                    cp_name,
+                    CaptureEventKind::Start {
+                        owner: Team::new(*team),
+                        team: Team::new(*cap_team),
+                        cappers: cappers_from_event(cappers),
+                        cap_time: *cap_time,
+                    },
+                ),
+                GameEvent::TeamPlayPointCaptured(TeamPlayPointCapturedEvent {
+                    cp,
+                    cp_name,
+                    team,
+                    cappers,
+                }) => {
+                    if let Some(point) = self.state.control_points.get_mut(*cp as usize) {
+                        point.owner = Team::new(*team);
+                    }
+                    self.log_capture_event(
+                        *cp,
+                        cp_name,
+                        CaptureEventKind::Captured {
+                            team: Team::new(*team),
+                            cappers: cappers_from_event(cappers),
+                        },
+                    )
+                }
+                GameEvent::TeamPlayCaptureBlocked(TeamPlayCaptureBlockedEvent {
+                    cp,
+                    cp_name,
+                    blocker,
+                    victim,
+                }) => self.log_capture_event(
+                    *cp,
+                    cp_name,
+                    CaptureEventKind::Blocked {
+                        blocker: EntityId::from(*blocker as u32),
+                        victim: (*victim > 0).then(|| EntityId::from(*victim as u32)),
+                    },
+                ),
+                GameEvent::TeamPlayFlagEvent(TeamPlayFlagEventEvent {
+                    player,
+                    event_type,
+                    team,
+                    ..
+                }) => {
+                    if let Some(action) = FlagAction::new(*event_type) {
+                        self.log_objective_event(ObjectiveEventKind::Flag {
+                            player: event_entity(*player),
+                            team: Team::new(*team),
+                            action,
+                        })
+                    }
+                }
+                GameEvent::PassGet(PassGetEvent { owner }) => {
+                    self.log_objective_event(ObjectiveEventKind::BallPickup {
+                        player: event_entity(*owner),
+                    })
+                }
+                GameEvent::PassPassCaught(PassPassCaughtEvent {
+                    passer,
+                    catcher,
+                    dist,
+                    duration,
+                }) => {
+                    if let Some(ball) = self.state.ball.as_mut() {
+                        ball.passes += 1;
+                    }
+                    self.log_objective_event(ObjectiveEventKind::BallPass {
+                        passer: event_entity(*passer),
+                        catcher: event_entity(*catcher),
+                        distance: *dist,
+                        duration: *duration,
+                    })
+                }
+                GameEvent::PassFree(PassFreeEvent { owner, attacker }) => {
+                    self.log_objective_event(ObjectiveEventKind::BallDrop {
+                        player: event_entity(*owner),
+                        attacker: (*attacker > 0).then(|| event_entity(*attacker)),
+                    })
+                }
+                GameEvent::PassBallStolen(PassBallStolenEvent { victim, attacker }) => self
+                    .log_objective_event(ObjectiveEventKind::BallSteal {
+                        victim: event_entity(*victim),
+                        attacker: event_entity(*attacker),
+                    }),
+                GameEvent::PassBallBlocked(PassBallBlockedEvent { owner, blocker }) => self
+                    .log_objective_event(ObjectiveEventKind::BallBlock {
+                        player: event_entity(*owner),
+                        blocker: event_entity(*blocker),
+                    }),
+                GameEvent::PassScore(PassScoreEvent {
+                    scorer,
+                    assister,
+                    points,
+                }) => self.log_objective_event(ObjectiveEventKind::BallScore {
+                    scorer: event_entity(*scorer),
+                    assister: (*assister > 0).then(|| event_entity(*assister)),
+                    points: *points,
+                }),
                 _ => {}
             },
             _ => {}
@@ -79,12 +221,11 @@ impl MessageHandler for GameStateAnalyser {
         entry: &StringTableEntry,
         _parser_state: &ParserState,
     ) {
-        if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+        if let Some((_, user_info)) =
+            crate::demo::data::UserInfo::from_string_entry(table, index, entry)
+        {
+            let id = user_info.entity_id;
+            self.state.get_or_create_player(id).info = Some(user_info.into());
         }
     }
 
@@ -99,6 +240,7 @@ impl MessageHandler for GameStateAnalyser {
             .map(|class| &class.name)
             .cloned()
             .collect();
+        self.weapon_classes.clear();
     }
 
     fn handle_packet_meta(
@@ -111,6 +253,31 @@ impl MessageHandler for GameStateAnalyser {
         self.tick = tick;
     }
 
+    fn handle_map_change(
+        &mut self,
+        _server_info: &ServerInfoMessage,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+        // entities from the previous map are gone, players are kept since they are re-used by entity id
+        self.state.buildings.clear();
+        self.state.projectiles.clear();
+        self.state.mediguns.clear();
+        self.state.weapons.clear();
+        self.state.control_points.clear();
+        self.state.playing_mini_rounds = false;
+        self.state.hud_round_timer = None;
+        self.state.payloads.clear();
+        self.state.trains.clear();
+        self.state.flags.clear();
+        self.state.ball = None;
+        self.state.round_timers.clear();
+        self.state.teams.clear();
+        self.koth_timers = Default::default();
+        self.state.entity_serials.clear();
+        self.state.world = None;
+    }
+
     fn into_output(mut self, state: &ParserState) -> Self::Output {
         self.state.server_classes = state.server_classes.clone();
         self.state
@@ -129,22 +296,26 @@ impl GameStateAnalyser {
     }
 
     pub fn handle_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
-        const OUTER: SendPropIdentifier =
-            SendPropIdentifier::new("DT_AttributeContainer", "m_hOuter");
+        match entity.update_type {
+            UpdateType::Enter => {
+                self.state
+                    .entity_serials
+                    .insert(entity.entity_index, entity.serial_number as u16);
+            }
+            UpdateType::Delete => {
+                self.state.entity_serials.remove(&entity.entity_index);
+            }
+            _ => {}
+        }
+
+        if self.is_weapon_class(entity.server_class, parser_state) {
+            self.handle_weapon_entity(entity, parser_state);
+        }
 
         let Some(class_name) = self.class_names.get(usize::from(entity.server_class)) else {
             return;
         };
 
-        for prop in &entity.props {
-            if prop.identifier == OUTER {
-                let outer = i64::try_from(&prop.value).unwrap_or_default();
-                self.state
-                    .outer_map
-                    .insert(Handle(outer), entity.entity_index);
-            }
-        }
-
         match class_name.as_str() {
             "CTFPlayer" => self.handle_player_entity(entity, parser_state),
             "CTFPlayerResource" => self.handle_player_resource(entity, parser_state),
@@ -152,6 +323,15 @@ impl GameStateAnalyser {
             "CObjectSentrygun" => self.handle_sentry_entity(entity, parser_state),
             "CObjectDispenser" => self.handle_dispenser_entity(entity, parser_state),
             "CObjectTeleporter" => self.handle_teleporter_entity(entity, parser_state),
+            "CWeaponMedigun" => self.handle_medigun_entity(entity, parser_state),
+            "CTFObjectiveResource" => self.handle_objective_resource(entity, parser_state),
+            "CTeamTrainWatcher" => self.handle_train_watcher(entity, parser_state),
+            "CFuncTrackTrain" => self.handle_train(entity, parser_state),
+            "CCaptureFlag" => self.handle_flag_entity(entity, parser_state),
+            "CPasstimeBall" => self.handle_ball_entity(entity, parser_state),
+            "CTeamRoundTimer" => self.handle_round_timer(entity, parser_state),
+            "CTFGameRulesProxy" => self.handle_game_rules(entity, parser_state),
+            "CTFTeam" => update_team_state(&mut self.state.teams, entity, parser_state),
             _ if class_name.starts_with("CTFProjectile_")
                 || class_name.as_str() == "CTFGrenadePipebombProjectile" =>
             {
@@ -231,11 +411,22 @@ impl GameStateAnalyser {
         const PROP_BB_MAX: SendPropIdentifier =
             SendPropIdentifier::new("DT_CollisionProperty", "m_vecMaxsPreScaled");
 
-        const WEAPON_0: SendPropIdentifier = SendPropIdentifier::new("m_hMyWeapons", "000");
-        const WEAPON_1: SendPropIdentifier = SendPropIdentifier::new("m_hMyWeapons", "001");
-        const WEAPON_2: SendPropIdentifier = SendPropIdentifier::new("m_hMyWeapons", "002");
+        const ACTIVE_WEAPON: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseCombatCharacter", "m_hActiveWeapon");
+
+        const COND: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCond");
+        const COND_BITS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFPlayerConditionListExclusive", "_condition_bits");
+        const COND_EX: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx");
+        const COND_EX_2: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx2");
+        const COND_EX_3: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx3");
 
         player.in_pvs = entity.in_pvs;
+        let old_conditions = player.conditions;
 
         for prop in entity.props(parser_state) {
             match prop.identifier {
@@ -269,21 +460,75 @@ impl GameStateAnalyser {
                     let max = Vector::try_from(&prop.value).unwrap_or_default();
                     player.bounds.max = max;
                 }
-                WEAPON_0 => {
-                    let handle = Handle(i64::try_from(&prop.value).unwrap_or_default());
-                    player.weapons[0] = handle;
+                ACTIVE_WEAPON => {
+                    let handle = Handle::from(i64::try_from(&prop.value).unwrap_or_default());
+                    if handle != player.active_weapon {
+                        player.active_weapon = handle;
+                        self.pending_switches.push(entity.entity_index);
+                    }
                 }
-                WEAPON_1 => {
-                    let handle = Handle(i64::try_from(&prop.value).unwrap_or_default());
-                    player.weapons[1] = handle;
+                COND | COND_BITS | COND_EX | COND_EX_2 | COND_EX_3 => {
+                    let word = match prop.identifier {
+                        COND_EX => 1,
+                        COND_EX_2 => 2,
+                        COND_EX_3 => 3,
+                        _ => 0,
+                    };
+                    let bits = i64::try_from(&prop.value).unwrap_or_default() as u32;
+                    player.conditions.set_word(word, bits);
                 }
-                WEAPON_2 => {
-                    let handle = Handle(i64::try_from(&prop.value).unwrap_or_default());
-                    player.weapons[2] = handle;
+                _ => {
+                    // weapon handles and ammo counts are array elements named by their index
+                    let Some((table_name, prop_name)) = prop.identifier.names() else {
+                        continue;
+                    };
+                    let Ok(index) = usize::from_str(prop_name.as_str()) else {
+                        continue;
+                    };
+                    let value = i64::try_from(&prop.value).unwrap_or_default();
+                    match table_name.as_str() {
+                        "m_hMyWeapons" => match Handle::from(value) {
+                            handle if handle.is_valid() => {
+                                player.weapons.insert(index, handle);
+                            }
+                            // the weapon in this slot was removed
+                            _ => {
+                                player.weapons.remove(&index);
+                            }
+                        },
+                        "m_iAmmo" => {
+                            if player.ammo.len() <= index {
+                                player.ammo.resize(index + 1, 0);
+                            }
+                            if let Some(ammo) = player.ammo.get_mut(index) {
+                                *ammo = value.max(0) as u32;
+                            }
+                        }
+                        _ => {}
+                    }
                 }
-                _ => {}
             }
         }
+
+        let conditions = player.conditions;
+        for (condition, active) in conditions
+            .difference(old_conditions)
+            .iter()
+            .map(|condition| (condition, true))
+            .chain(
+                old_conditions
+                    .difference(conditions)
+                    .iter()
+                    .map(|condition| (condition, false)),
+            )
+        {
+            self.state.condition_changes.push(ConditionChange {
+                tick: self.tick,
+                player: entity.entity_index,
+                condition,
+                active,
+            });
+        }
     }
 
     pub fn handle_world_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
@@ -343,7 +588,7 @@ impl GameStateAnalyser {
                     }
                     TARGET => {
                         sentry.auto_aim_target =
-                            UserId::from(i64::try_from(&prop.value).unwrap_or_default() as u16)
+                            Handle::from(i64::try_from(&prop.value).unwrap_or_default())
                     }
                     SHELLS => sentry.shells = i64::try_from(&prop.value).unwrap_or_default() as u16,
                     ROCKETS => {
@@ -448,6 +693,535 @@ impl GameStateAnalyser {
         }
     }
 
+    pub fn handle_medigun_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ITEM_INDEX: SendPropIdentifier =
+            SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+        const HEAL_TARGET: SendPropIdentifier =
+            SendPropIdentifier::new("DT_WeaponMedigun", "m_hHealingTarget");
+        const HEALING: SendPropIdentifier =
+            SendPropIdentifier::new("DT_WeaponMedigun", "m_bHealing");
+        const CHARGE_RELEASE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_WeaponMedigun", "m_bChargeRelease");
+        const HOLSTERED: SendPropIdentifier =
+            SendPropIdentifier::new("DT_WeaponMedigun", "m_bHolstered");
+        const RESIST_TYPE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_WeaponMedigun", "m_nChargeResistType");
+        const LOCAL_CHARGE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_LocalTFWeaponMedigunData", "m_flChargeLevel");
+        const NON_LOCAL_CHARGE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFWeaponMedigunDataNonLocal", "m_flChargeLevel");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.mediguns.remove(&entity.entity_index);
+            return;
+        }
+
+        let medigun = self
+            .state
+            .mediguns
+            .entry(entity.entity_index)
+            .or_insert_with(|| Medigun::new(entity.entity_index));
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ITEM_INDEX => {
+                    let item_index = i64::try_from(&prop.value).unwrap_or_default();
+                    medigun.item_index = item_index as u16;
+                    medigun.ty = MedigunType::new(item_index);
+                }
+                HEAL_TARGET => {
+                    medigun.heal_target =
+                        Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                HEALING => medigun.healing = i64::try_from(&prop.value).unwrap_or_default() > 0,
+                CHARGE_RELEASE => {
+                    medigun.charge_release = i64::try_from(&prop.value).unwrap_or_default() > 0
+                }
+                HOLSTERED => medigun.holstered = i64::try_from(&prop.value).unwrap_or_default() > 0,
+                RESIST_TYPE => {
+                    medigun.resist_type = i64::try_from(&prop.value).unwrap_or_default() as u8
+                }
+                LOCAL_CHARGE | NON_LOCAL_CHARGE => {
+                    medigun.charge = f32::try_from(&prop.value).unwrap_or_default()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn is_weapon_class(&mut self, class: ClassId, parser_state: &ParserState) -> bool {
+        const OWNER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseCombatWeapon", "m_hOwner");
+
+        *self
+            .weapon_classes
+            .entry(class)
+            .or_insert_with(|| parser_state.index_for_prop(class, OWNER).is_some())
+    }
+
+    pub fn handle_weapon_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ITEM_INDEX: SendPropIdentifier =
+            SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+        const OWNER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseCombatWeapon", "m_hOwner");
+        const CLIP: SendPropIdentifier = SendPropIdentifier::new("DT_LocalWeaponData", "m_iClip1");
+        const CLIP_2: SendPropIdentifier =
+            SendPropIdentifier::new("DT_LocalWeaponData", "m_iClip2");
+        const AMMO_TYPE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_LocalWeaponData", "m_iPrimaryAmmoType");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.weapons.remove(&entity.entity_index);
+            return;
+        }
+
+        let slot = self
+            .class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| WeaponSlot::new(class_name.as_str()))
+            .unwrap_or_default();
+        let weapon = self
+            .state
+            .weapons
+            .entry(entity.entity_index)
+            .or_insert_with(|| Weapon::new(entity.entity_index, entity.server_class, slot));
+        weapon.class = entity.server_class;
+        weapon.slot = slot;
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ITEM_INDEX => {
+                    weapon.item_index = i64::try_from(&prop.value).unwrap_or_default() as u16
+                }
+                OWNER => {
+                    weapon.owner = Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                // weapons without a clip network -1
+                CLIP => weapon.clip = u32::try_from(i64::try_from(&prop.value).unwrap_or(-1)).ok(),
+                CLIP_2 => {
+                    weapon.clip2 = u32::try_from(i64::try_from(&prop.value).unwrap_or(-1)).ok()
+                }
+                AMMO_TYPE => {
+                    weapon.ammo_type = u8::try_from(i64::try_from(&prop.value).unwrap_or(-1)).ok()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_weapon_switches(&mut self) {
+        for player in self.pending_switches.drain(..) {
+            let Some(handle) = self
+                .state
+                .get_player(player)
+                .map(|player| player.active_weapon)
+            else {
+                continue;
+            };
+            let weapon = self.state.get_weapon(handle);
+            self.state.weapon_switches.push(WeaponSwitch {
+                tick: self.tick,
+                player,
+                weapon: weapon.map(|weapon| weapon.entity),
+                item_index: weapon.map(|weapon| weapon.item_index),
+            });
+        }
+    }
+
+    pub fn handle_objective_resource(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const NUM_POINTS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseTeamObjectiveResource", "m_iNumControlPoints");
+        const POSITIONS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseTeamObjectiveResource", "m_vCPPositions");
+        const MINI_ROUNDS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseTeamObjectiveResource", "m_bPlayingMiniRounds");
+        const HUD_TIMER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseTeamObjectiveResource", "m_iTimerToShowInHUD");
+
+        // the arrays are always sent for all possible points, only keep the ones used by the map
+        if let Some(prop) = entity
+            .props(parser_state)
+            .find(|prop| prop.identifier == NUM_POINTS)
+        {
+            let count = i64::try_from(&prop.value).unwrap_or_default() as usize;
+            let count = count.min(MAX_CONTROL_POINTS);
+            self.state.control_points.truncate(count);
+            if let Some(last) = count.checked_sub(1) {
+                self.state.get_or_create_control_point(last as u8);
+            }
+        }
+
+        for prop in entity.props(parser_state) {
+            if prop.identifier == POSITIONS {
+                if let SendPropValue::Array(positions) = &prop.value {
+                    for (point, position) in
+                        self.state.control_points.iter_mut().zip(positions.iter())
+                    {
+                        point.position = Vector::try_from(position).unwrap_or_default();
+                    }
+                }
+                continue;
+            }
+            if prop.identifier == MINI_ROUNDS {
+                self.state.playing_mini_rounds = i64::try_from(&prop.value).unwrap_or_default() > 0;
+                continue;
+            }
+            if prop.identifier == HUD_TIMER {
+                // entity index of the timer, 0 when no timer is shown
+                self.state.hud_round_timer = match i64::try_from(&prop.value).unwrap_or_default() {
+                    0 => None,
+                    timer => Some(EntityId::from(timer as u32)),
+                };
+                continue;
+            }
+
+            let Some((table_name, prop_name)) = prop.identifier.names() else {
+                continue;
+            };
+            let Ok(index) = usize::from_str(prop_name.as_str()) else {
+                continue;
+            };
+            // team member counts and capture permissions are stored per team, then per point
+            let (index, team) = match table_name.as_str() {
+                "m_iNumTeamMembers" | "m_bTeamCanCap" => (
+                    index % MAX_CONTROL_POINTS,
+                    Team::new(index / MAX_CONTROL_POINTS),
+                ),
+                _ => (index, Team::Other),
+            };
+            let Some(point) = self.state.control_points.get_mut(index) else {
+                continue;
+            };
+
+            let value = i64::try_from(&prop.value).unwrap_or_default();
+            match table_name.as_str() {
+                "m_iOwner" => point.owner = Team::new(value),
+                "m_iCappingTeam" => point.capping_team = Team::new(value),
+                "m_iTeamInZone" => point.team_in_zone = Team::new(value),
+                "m_flLazyCapPerc" => {
+                    point.cap_progress = f32::try_from(&prop.value).unwrap_or_default()
+                }
+                "m_bCPLocked" => point.locked = value > 0,
+                "m_bBlocked" => point.blocked = value > 0,
+                "m_bCPIsVisible" => point.visible = value > 0,
+                "m_bInMiniRound" => point.in_mini_round = value > 0,
+                "m_bTeamCanCap" => match team {
+                    Team::Red => point.red_can_cap = value > 0,
+                    Team::Blue => point.blue_can_cap = value > 0,
+                    _ => {}
+                },
+                "m_iNumTeamMembers" => match team {
+                    Team::Red => point.red_on_point = value as u8,
+                    Team::Blue => point.blue_on_point = value as u8,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    pub fn handle_train_watcher(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+        const PROGRESS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_flTotalProgress");
+        const SPEED_LEVEL: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_iTrainSpeedLevel");
+        const RECEDE_TIME: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_flRecedeTime");
+        const CAPPERS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_nNumCappers");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.payloads.remove(&entity.entity_index);
+            return;
+        }
+
+        let cart = self
+            .state
+            .payloads
+            .entry(entity.entity_index)
+            .or_insert_with(|| PayloadCart {
+                watcher: entity.entity_index,
+                ..PayloadCart::default()
+            });
+        let previous = (cart.progress, cart.cappers);
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                TEAM => cart.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                PROGRESS => cart.progress = f32::try_from(&prop.value).unwrap_or_default(),
+                SPEED_LEVEL => {
+                    cart.speed_level = i64::try_from(&prop.value).unwrap_or_default() as i8
+                }
+                RECEDE_TIME => cart.recede_time = f32::try_from(&prop.value).unwrap_or_default(),
+                CAPPERS => cart.cappers = i64::try_from(&prop.value).unwrap_or_default() as u8,
+                _ => {}
+            }
+        }
+
+        if previous != (cart.progress, cart.cappers) {
+            self.state.payload_progress.push(PayloadProgress {
+                tick: self.tick,
+                round: self.state.round,
+                watcher: cart.watcher,
+                progress: cart.progress,
+                cappers: cart.cappers,
+            });
+        }
+        self.update_payload_positions();
+    }
+
+    pub fn handle_train(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.trains.remove(&entity.entity_index);
+            return;
+        }
+
+        let train = self
+            .state
+            .trains
+            .entry(entity.entity_index)
+            .or_insert_with(|| Train {
+                entity: entity.entity_index,
+                ..Train::default()
+            });
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ORIGIN => train.position = Vector::try_from(&prop.value).unwrap_or_default(),
+                TEAM => train.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                _ => {}
+            }
+        }
+        self.update_payload_positions();
+    }
+
+    fn update_payload_positions(&mut self) {
+        let positions: Vec<(EntityId, Vector)> = self
+            .state
+            .payloads
+            .values()
+            .filter_map(|cart| Some((cart.watcher, self.state.get_payload_train(cart)?.position)))
+            .collect();
+        for (watcher, position) in positions {
+            if let Some(cart) = self.state.payloads.get_mut(&watcher) {
+                cart.position = position;
+            }
+        }
+    }
+
+    pub fn handle_flag_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+        const OWNER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseEntity", "m_hOwnerEntity");
+        const STATUS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_CaptureFlag", "m_nFlagStatus");
+        const RESET_TIME: SendPropIdentifier =
+            SendPropIdentifier::new("DT_CaptureFlag", "m_flResetTime");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.flags.remove(&entity.entity_index);
+            return;
+        }
+
+        let flag = self
+            .state
+            .flags
+            .entry(entity.entity_index)
+            .or_insert_with(|| Flag {
+                entity: entity.entity_index,
+                ..Flag::default()
+            });
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ORIGIN => flag.position = Vector::try_from(&prop.value).unwrap_or_default(),
+                TEAM => flag.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                OWNER => {
+                    flag.carrier = Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                STATUS => {
+                    flag.status = FlagStatus::new(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                RESET_TIME => flag.reset_time = f32::try_from(&prop.value).unwrap_or_default(),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn handle_ball_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+        const CARRIER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_PasstimeBall", "m_hCarrier");
+        const PREVIOUS_CARRIER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_PasstimeBall", "m_hPrevCarrier");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.ball = None;
+            return;
+        }
+
+        let ball = match &mut self.state.ball {
+            Some(ball) if ball.entity == entity.entity_index => ball,
+            ball => ball.insert(PasstimeBall {
+                entity: entity.entity_index,
+                ..PasstimeBall::default()
+            }),
+        };
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ORIGIN => ball.position = Vector::try_from(&prop.value).unwrap_or_default(),
+                CARRIER => {
+                    ball.carrier = Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                PREVIOUS_CARRIER => {
+                    ball.previous_carrier =
+                        Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn handle_round_timer(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const PAUSED: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_bTimerPaused");
+        const TIME_REMAINING: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_flTimeRemaining");
+        const END_TIME: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_flTimerEndTime");
+        const DISABLED: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_bIsDisabled");
+        const SHOW_IN_HUD: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_bShowInHUD");
+        const LENGTH: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_nTimerLength");
+        const SETUP_LENGTH: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamRoundTimer", "m_nSetupTimeLength");
+        const STATE: SendPropIdentifier = SendPropIdentifier::new("DT_TeamRoundTimer", "m_nState");
+
+        if entity.update_type == UpdateType::Delete {
+            self.state.round_timers.remove(&entity.entity_index);
+            return;
+        }
+
+        let timer = self
+            .state
+            .round_timers
+            .entry(entity.entity_index)
+            .or_insert_with(|| RoundTimer {
+                entity: entity.entity_index,
+                ..RoundTimer::default()
+            });
+        let previous = timer.clone();
+
+        for prop in entity.props(parser_state) {
+            let value = i64::try_from(&prop.value).unwrap_or_default();
+            match prop.identifier {
+                PAUSED => timer.paused = value > 0,
+                TIME_REMAINING => {
+                    timer.time_remaining = f32::try_from(&prop.value).unwrap_or_default()
+                }
+                END_TIME => timer.end_time = f32::try_from(&prop.value).unwrap_or_default(),
+                DISABLED => timer.disabled = value > 0,
+                SHOW_IN_HUD => timer.show_in_hud = value > 0,
+                LENGTH => timer.length = value as u32,
+                SETUP_LENGTH => timer.setup_length = value as u32,
+                STATE => timer.state = RoundTimerState::new(value),
+                _ => {}
+            }
+        }
+
+        if entity.update_type == UpdateType::Enter || *timer != previous {
+            let timer = timer.clone();
+            self.push_timer_update(timer);
+        }
+    }
+
+    pub fn handle_game_rules(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const RED_KOTH_TIMER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFGameRules", "m_hRedKothTimer");
+        const BLUE_KOTH_TIMER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFGameRules", "m_hBlueKothTimer");
+
+        let previous = self.koth_timers;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                RED_KOTH_TIMER => {
+                    self.koth_timers[0] =
+                        Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                BLUE_KOTH_TIMER => {
+                    self.koth_timers[1] =
+                        Handle::from(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+
+        if previous != self.koth_timers {
+            let changed: Vec<RoundTimer> = self
+                .state
+                .round_timers
+                .values()
+                .filter(|timer| timer.team != self.koth_team(timer.entity))
+                .cloned()
+                .collect();
+            for timer in changed {
+                self.push_timer_update(timer);
+            }
+        }
+    }
+
+    fn koth_team(&self, timer: EntityId) -> Team {
+        let is_timer = |handle: Handle| self.state.resolve_handle(handle) == Some(timer);
+        match self.koth_timers {
+            [red, _] if is_timer(red) => Team::Red,
+            [_, blue] if is_timer(blue) => Team::Blue,
+            _ => Team::Other,
+        }
+    }
+
+    /// Assign the koth team to a timer and record the new state of the timer
+    fn push_timer_update(&mut self, mut timer: RoundTimer) {
+        timer.team = self.koth_team(timer.entity);
+        if let Some(stored) = self.state.round_timers.get_mut(&timer.entity) {
+            stored.team = timer.team;
+        }
+
+        let server_time = u32::from(self.server_tick) as f32 * self.state.interval_per_tick;
+        self.state.round_timer_history.push(RoundTimerUpdate {
+            tick: self.tick,
+            server_time,
+            timer,
+        });
+    }
+
+    fn log_objective_event(&mut self, kind: ObjectiveEventKind) {
+        self.state.objective_events.push(ObjectiveEvent {
+            tick: self.tick,
+            kind,
+        });
+    }
+
+    fn log_capture_event(&mut self, point: u8, name: &MaybeUtf8String, kind: CaptureEventKind) {
+        let point_name = name.as_ref().to_string();
+        if let Some(control_point) = self.state.control_points.get_mut(point as usize) {
+            control_point.name.clone_from(&point_name);
+        }
+        self.state.capture_log.push(CaptureEvent {
+            tick: self.tick,
+            point,
+            point_name,
+            kind,
+        });
+    }
+
     fn handle_building(
         &mut self,
         entity: &PacketEntity,
@@ -520,8 +1294,7 @@ impl GameStateAnalyser {
                         BUILDING => *building = i64::try_from(&prop.value).unwrap_or_default() > 0,
                         LEVEL => *level = i64::try_from(&prop.value).unwrap_or_default() as u8,
                         BUILDER => {
-                            *builder =
-                                UserId::from(i64::try_from(&prop.value).unwrap_or_default() as u16)
+                            *builder = Handle::from(i64::try_from(&prop.value).unwrap_or_default())
                         }
                         MAX_HEALTH => {
                             *max_health = i64::try_from(&prop.value).unwrap_or_default() as u16
@@ -586,7 +1359,7 @@ impl GameStateAnalyser {
                     projectile.initial_speed = speed;
                 }
                 LAUNCHER => {
-                    let launcher = Handle(i64::try_from(&prop.value).unwrap_or_default());
+                    let launcher = Handle::from(i64::try_from(&prop.value).unwrap_or_default());
                     projectile.launcher = launcher;
                 }
                 PIPE_TYPE => {
@@ -605,20 +1378,830 @@ impl GameStateAnalyser {
             }
         }
     }
+}
 
-    fn parse_user_info(
-        &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            let id = user_info.entity_id;
-            self.state.get_or_create_player(id).info = Some(user_info.into());
+/// Update the team state from a `CTFTeam` entity
+pub fn update_team_state(
+    teams: &mut Vec<TeamState>,
+    entity: &PacketEntity,
+    parser_state: &ParserState,
+) {
+    const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_iTeamNum");
+    const SCORE: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_iScore");
+    const ROUNDS_WON: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_iRoundsWon");
+    const NAME: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_szTeamname");
+    const PLAYERS: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "\"player_array\"");
+    const FLAG_CAPTURES: SendPropIdentifier =
+        SendPropIdentifier::new("DT_TFTeam", "m_nFlagCaptures");
+
+    if entity.update_type == UpdateType::Delete {
+        teams.retain(|team| team.entity != entity.entity_index);
+        return;
+    }
+
+    let team = match teams
+        .iter()
+        .position(|team| team.entity == entity.entity_index)
+    {
+        Some(index) => index,
+        None => {
+            teams.push(TeamState {
+                entity: entity.entity_index,
+                ..TeamState::default()
+            });
+            teams.len() - 1
         }
+    };
+    let Some(team) = teams.get_mut(team) else {
+        return;
+    };
+
+    for prop in entity.props(parser_state) {
+        match prop.identifier {
+            TEAM => team.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+            SCORE => team.score = i64::try_from(&prop.value).unwrap_or_default() as u32,
+            ROUNDS_WON => team.rounds_won = i64::try_from(&prop.value).unwrap_or_default() as u32,
+            FLAG_CAPTURES => {
+                team.flag_captures = i64::try_from(&prop.value).unwrap_or_default() as u32
+            }
+            NAME => {
+                if let SendPropValue::String(name) = &prop.value {
+                    team.name.clone_from(name);
+                }
+            }
+            PLAYERS => {
+                if let SendPropValue::Array(players) = &prop.value {
+                    team.players = players
+                        .iter()
+                        .map(|player| {
+                            EntityId::from(i64::try_from(player).unwrap_or_default() as u32)
+                        })
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Flag and passtime events identify players by their entity index
+fn event_entity(index: u16) -> EntityId {
+    EntityId::from(index as u32)
+}
+
+/// The cappers in capture events are sent as a string with a character for every player entity
+fn cappers_from_event(cappers: &MaybeUtf8String) -> Vec<EntityId> {
+    let bytes = match cappers {
+        MaybeUtf8String::Valid(cappers) => cappers.as_bytes(),
+        MaybeUtf8String::Invalid(cappers) => cappers.as_slice(),
+    };
+    bytes
+        .iter()
+        .map(|player| EntityId::from(*player as u32))
+        .collect()
+}
+
+#[test]
+fn test_capture_log() {
+    let mut analyser = GameStateAnalyser::new();
+    analyser.state.get_or_create_control_point(1).owner = Team::Red;
+    analyser.tick = DemoTick::from(50u32);
+
+    let cappers = cappers_from_event(&MaybeUtf8String::from("\u{2}\u{5}"));
+    assert_eq!(vec![EntityId::from(2u32), EntityId::from(5u32)], cappers);
+
+    analyser.log_capture_event(
+        1,
+        &MaybeUtf8String::from("#Gravelpit_cap_B"),
+        CaptureEventKind::Captured {
+            team: Team::Blue,
+            cappers,
+        },
+    );
+    analyser.log_capture_event(
+        6,
+        &MaybeUtf8String::from("unknown"),
+        CaptureEventKind::Blocked {
+            blocker: EntityId::from(3u32),
+            victim: None,
+        },
+    );
+
+    assert_eq!(2, analyser.state.control_points.len());
+    let point = analyser.state.get_control_point(1).unwrap();
+    assert_eq!("#Gravelpit_cap_B", point.name);
+    assert_eq!(2, analyser.state.capture_log.len());
+    assert_eq!(DemoTick::from(50u32), analyser.state.capture_log[0].tick);
+    assert_eq!(6, analyser.state.capture_log[1].point);
+}
+
+#[cfg(test)]
+fn test_event(event: GameEvent) -> Message<'static> {
+    Message::GameEvent(GameEventMessage {
+        event_type_id: 0u16.into(),
+        event_type: event.event_type(),
+        event,
+    })
+}
+
+#[test]
+fn test_objective_resource() {
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+    let position = |x: f32| Vector { x, y: 64.0, z: 0.0 };
+
+    let mut positions = vec![
+        SendPropValue::Vector(position(-512.0)),
+        SendPropValue::Vector(position(0.0)),
+        SendPropValue::Vector(position(512.0)),
+    ];
+    positions.resize(MAX_CONTROL_POINTS, SendPropValue::Vector(Vector::default()));
+    analyser.handle_objective_resource(
+        &test_entity(
+            40,
+            vec![
+                (
+                    "DT_BaseTeamObjectiveResource",
+                    "m_iNumControlPoints",
+                    SendPropValue::Integer(3),
+                ),
+                (
+                    "DT_BaseTeamObjectiveResource",
+                    "m_vCPPositions",
+                    SendPropValue::Array(positions),
+                ),
+                ("m_iOwner", "000", SendPropValue::Integer(2)),
+                ("m_iOwner", "002", SendPropValue::Integer(3)),
+                ("m_iCappingTeam", "001", SendPropValue::Integer(3)),
+                ("m_iTeamInZone", "001", SendPropValue::Integer(3)),
+                ("m_flLazyCapPerc", "001", SendPropValue::Float(0.5)),
+                ("m_bCPLocked", "002", SendPropValue::Integer(1)),
+                ("m_bInMiniRound", "001", SendPropValue::Integer(1)),
+                ("m_bTeamCanCap", "016", SendPropValue::Integer(1)),
+                ("m_bTeamCanCap", "025", SendPropValue::Integer(1)),
+                (
+                    "DT_BaseTeamObjectiveResource",
+                    "m_bPlayingMiniRounds",
+                    SendPropValue::Integer(1),
+                ),
+                (
+                    "DT_BaseTeamObjectiveResource",
+                    "m_iTimerToShowInHUD",
+                    SendPropValue::Integer(948),
+                ),
+                // red is team 2, so the red counts start at 2 * MAX_CONTROL_POINTS
+                ("m_iNumTeamMembers", "017", SendPropValue::Integer(1)),
+                // blue is team 3
+                ("m_iNumTeamMembers", "025", SendPropValue::Integer(2)),
+                // points past m_iNumControlPoints are ignored
+                ("m_iOwner", "005", SendPropValue::Integer(2)),
+            ],
+        ),
+        &parser_state,
+    );
+
+    let points = &analyser.state.control_points;
+    assert_eq!(3, points.len());
+    assert_eq!(
+        vec![position(-512.0), position(0.0), position(512.0)],
+        points
+            .iter()
+            .map(|point| point.position)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![Team::Red, Team::Other, Team::Blue],
+        points.iter().map(|point| point.owner).collect::<Vec<_>>()
+    );
+    assert_eq!(Team::Blue, points[1].capping_team);
+    assert_eq!(Team::Blue, points[1].team_in_zone);
+    assert_eq!(0.5, points[1].cap_progress);
+    assert_eq!(1, points[1].red_on_point);
+    assert_eq!(2, points[1].blue_on_point);
+    assert_eq!(0, points[0].blue_on_point);
+    assert!(points[2].locked);
+    assert!(points[1].in_mini_round);
+    assert!(!points[0].in_mini_round);
+    assert!(points[0].red_can_cap);
+    assert!(!points[0].blue_can_cap);
+    assert!(points[1].blue_can_cap);
+    assert!(analyser.state.playing_mini_rounds);
+    assert_eq!(Some(EntityId::from(948u32)), analyser.state.hud_round_timer);
+
+    analyser.tick = DemoTick::from(30u32);
+    analyser.handle_message(
+        &test_event(GameEvent::TeamPlayPointStartCapture(
+            TeamPlayPointStartCaptureEvent {
+                cp: 1,
+                cp_name: "#koth_viaduct_cap".into(),
+                team: 0,
+                cap_team: 3,
+                cappers: "\u{4}\u{7}".into(),
+                cap_time: 6.0,
+            },
+        )),
+        DemoTick::from(30u32),
+        &parser_state,
+    );
+    analyser.tick = DemoTick::from(40u32);
+    analyser.handle_message(
+        &test_event(GameEvent::TeamPlayCaptureBlocked(
+            TeamPlayCaptureBlockedEvent {
+                cp: 1,
+                cp_name: "#koth_viaduct_cap".into(),
+                blocker: 9,
+                victim: 0,
+            },
+        )),
+        DemoTick::from(40u32),
+        &parser_state,
+    );
+    analyser.tick = DemoTick::from(50u32);
+    analyser.handle_message(
+        &test_event(GameEvent::TeamPlayPointCaptured(
+            TeamPlayPointCapturedEvent {
+                cp: 1,
+                cp_name: "#koth_viaduct_cap".into(),
+                team: 3,
+                cappers: "\u{4}\u{7}".into(),
+            },
+        )),
+        DemoTick::from(50u32),
+        &parser_state,
+    );
+
+    let point = analyser.state.get_control_point(1).unwrap();
+    assert_eq!(Team::Blue, point.owner);
+    assert_eq!("#koth_viaduct_cap", point.name);
+
+    let cappers = vec![EntityId::from(4u32), EntityId::from(7u32)];
+    let log = &analyser.state.capture_log;
+    assert_eq!(3, log.len());
+    assert_eq!(
+        CaptureEventKind::Start {
+            owner: Team::Other,
+            team: Team::Blue,
+            cappers: cappers.clone(),
+            cap_time: 6.0,
+        },
+        log[0].kind
+    );
+    assert_eq!(
+        CaptureEventKind::Blocked {
+            blocker: EntityId::from(9u32),
+            victim: None,
+        },
+        log[1].kind
+    );
+    assert_eq!(DemoTick::from(50u32), log[2].tick);
+    assert_eq!(
+        CaptureEventKind::Captured {
+            team: Team::Blue,
+            cappers,
+        },
+        log[2].kind
+    );
+
+    // the map now only uses two points
+    analyser.handle_objective_resource(
+        &test_entity(
+            40,
+            vec![(
+                "DT_BaseTeamObjectiveResource",
+                "m_iNumControlPoints",
+                SendPropValue::Integer(2),
+            )],
+        ),
+        &parser_state,
+    );
+    assert_eq!(2, analyser.state.control_points.len());
+    assert_eq!(Team::Blue, analyser.state.control_points[1].owner);
+}
+
+#[cfg(test)]
+fn test_entity(index: u32, props: Vec<(&str, &str, SendPropValue)>) -> PacketEntity {
+    PacketEntity {
+        server_class: 0u16.into(),
+        entity_index: index.into(),
+        props: props
+            .into_iter()
+            .map(|(table, name, value)| SendProp {
+                index: 0,
+                identifier: SendPropIdentifier::new(table, name),
+                value,
+            })
+            .collect(),
+        in_pvs: true,
+        update_type: UpdateType::Preserve,
+        serial_number: 0,
+        delay: None,
+        delta: None,
+        baseline_index: Default::default(),
+    }
+}
+
+#[test]
+fn test_payload_progress() {
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+    analyser.state.round = 2;
+
+    let position = Vector {
+        x: 100.0,
+        y: 200.0,
+        z: 0.0,
+    };
+    analyser.handle_train(
+        &test_entity(
+            80,
+            vec![(
+                "DT_BaseEntity",
+                "m_vecOrigin",
+                SendPropValue::Vector(position),
+            )],
+        ),
+        &parser_state,
+    );
+    analyser.handle_train_watcher(
+        &test_entity(
+            90,
+            vec![
+                ("DT_BaseEntity", "m_iTeamNum", SendPropValue::Integer(3)),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_flTotalProgress",
+                    SendPropValue::Float(0.25),
+                ),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_nNumCappers",
+                    SendPropValue::Integer(2),
+                ),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_iTrainSpeedLevel",
+                    SendPropValue::Integer(2),
+                ),
+            ],
+        ),
+        &parser_state,
+    );
+    analyser.tick = DemoTick::from(10u32);
+    analyser.handle_train_watcher(
+        &test_entity(
+            90,
+            vec![
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_nNumCappers",
+                    SendPropValue::Integer(0),
+                ),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_iTrainSpeedLevel",
+                    SendPropValue::Integer(-1),
+                ),
+            ],
+        ),
+        &parser_state,
+    );
 
-        Ok(())
+    let cart = &analyser.state.payloads[&EntityId::from(90u32)];
+    assert_eq!(Team::Blue, cart.team);
+    assert_eq!(position, cart.position);
+    assert_eq!(0.25, cart.progress);
+    assert!(cart.is_receding());
+
+    let progress = &analyser.state.payload_progress;
+    assert_eq!(2, progress.len());
+    assert_eq!(2, progress[0].round);
+    assert_eq!(2, progress[0].cappers);
+    assert_eq!(DemoTick::from(10u32), progress[1].tick);
+    assert_eq!(0, progress[1].cappers);
+}
+
+#[test]
+fn test_carried_objectives() {
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+
+    analyser.handle_flag_entity(
+        &test_entity(
+            60,
+            vec![
+                ("DT_BaseEntity", "m_iTeamNum", SendPropValue::Integer(2)),
+                (
+                    "DT_BaseEntity",
+                    "m_hOwnerEntity",
+                    SendPropValue::Integer(1234),
+                ),
+                ("DT_CaptureFlag", "m_nFlagStatus", SendPropValue::Integer(1)),
+            ],
+        ),
+        &parser_state,
+    );
+    analyser.handle_ball_entity(
+        &test_entity(
+            70,
+            vec![(
+                "DT_PasstimeBall",
+                "m_hCarrier",
+                SendPropValue::Integer(4321),
+            )],
+        ),
+        &parser_state,
+    );
+    let events = [
+        (
+            20u32,
+            GameEvent::TeamPlayFlagEvent(TeamPlayFlagEventEvent {
+                player: 3,
+                carrier: 3,
+                event_type: 1,
+                home: 0,
+                team: 2,
+            }),
+        ),
+        // unknown flag event types are ignored
+        (
+            21,
+            GameEvent::TeamPlayFlagEvent(TeamPlayFlagEventEvent {
+                player: 3,
+                carrier: 3,
+                event_type: 6,
+                home: 0,
+                team: 2,
+            }),
+        ),
+        (30, GameEvent::PassGet(PassGetEvent { owner: 5 })),
+        (
+            35,
+            GameEvent::PassPassCaught(PassPassCaughtEvent {
+                passer: 5,
+                catcher: 6,
+                dist: 512.0,
+                duration: 0.75,
+            }),
+        ),
+        (
+            40,
+            GameEvent::PassPassCaught(PassPassCaughtEvent {
+                passer: 6,
+                catcher: 5,
+                dist: 256.0,
+                duration: 0.5,
+            }),
+        ),
+        (
+            45,
+            GameEvent::PassFree(PassFreeEvent {
+                owner: 5,
+                attacker: 0,
+            }),
+        ),
+        (
+            50,
+            GameEvent::PassBallStolen(PassBallStolenEvent {
+                victim: 6,
+                attacker: 8,
+            }),
+        ),
+        (
+            55,
+            GameEvent::PassBallBlocked(PassBallBlockedEvent {
+                owner: 8,
+                blocker: 5,
+            }),
+        ),
+        (
+            60,
+            GameEvent::PassScore(PassScoreEvent {
+                scorer: 8,
+                assister: 0,
+                points: 1,
+            }),
+        ),
+    ];
+    for (tick, event) in events {
+        analyser.tick = DemoTick::from(tick);
+        analyser.handle_message(&test_event(event), DemoTick::from(tick), &parser_state);
+    }
+
+    let flag = &analyser.state.flags[&EntityId::from(60u32)];
+    assert_eq!(Team::Red, flag.team);
+    assert_eq!(FlagStatus::Stolen, flag.status);
+    assert_eq!(Handle::from(1234), flag.carrier);
+
+    let ball = analyser.state.ball.as_ref().unwrap();
+    assert_eq!(EntityId::from(70u32), ball.entity);
+    assert_eq!(Handle::from(4321), ball.carrier);
+    assert_eq!(2, ball.passes);
+
+    let events = &analyser.state.objective_events;
+    assert_eq!(8, events.len());
+    assert_eq!(DemoTick::from(20u32), events[0].tick);
+    assert_eq!(
+        ObjectiveEventKind::Flag {
+            player: EntityId::from(3u32),
+            team: Team::Red,
+            action: FlagAction::Pickup,
+        },
+        events[0].kind
+    );
+    assert_eq!(
+        ObjectiveEventKind::BallPickup {
+            player: EntityId::from(5u32),
+        },
+        events[1].kind
+    );
+    assert_eq!(DemoTick::from(35u32), events[2].tick);
+    assert_eq!(
+        ObjectiveEventKind::BallPass {
+            passer: EntityId::from(5u32),
+            catcher: EntityId::from(6u32),
+            distance: 512.0,
+            duration: 0.75,
+        },
+        events[2].kind
+    );
+    assert_eq!(
+        ObjectiveEventKind::BallDrop {
+            player: EntityId::from(5u32),
+            attacker: None,
+        },
+        events[4].kind
+    );
+    assert_eq!(
+        ObjectiveEventKind::BallSteal {
+            victim: EntityId::from(6u32),
+            attacker: EntityId::from(8u32),
+        },
+        events[5].kind
+    );
+    assert_eq!(
+        ObjectiveEventKind::BallBlock {
+            player: EntityId::from(8u32),
+            blocker: EntityId::from(5u32),
+        },
+        events[6].kind
+    );
+    assert_eq!(
+        ObjectiveEventKind::BallScore {
+            scorer: EntityId::from(8u32),
+            assister: None,
+            points: 1,
+        },
+        events[7].kind
+    );
+    assert_eq!(None, FlagAction::new(0));
+}
+
+#[test]
+fn test_team_state() {
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+    for (index, team, score, name) in [(2, 2, 3, "Froyotech"), (3, 3, 2, "")] {
+        update_team_state(
+            &mut analyser.state.teams,
+            &test_entity(
+                index,
+                vec![
+                    ("DT_Team", "m_iTeamNum", SendPropValue::Integer(team)),
+                    ("DT_Team", "m_iScore", SendPropValue::Integer(score)),
+                    ("DT_Team", "m_iRoundsWon", SendPropValue::Integer(1)),
+                    (
+                        "DT_Team",
+                        "m_szTeamname",
+                        SendPropValue::String(name.into()),
+                    ),
+                    (
+                        "DT_Team",
+                        "\"player_array\"",
+                        SendPropValue::Array(vec![
+                            SendPropValue::Integer(index as i64 * 2),
+                            SendPropValue::Integer(index as i64 * 2 + 1),
+                        ]),
+                    ),
+                    ("DT_TFTeam", "m_nFlagCaptures", SendPropValue::Integer(0)),
+                ],
+            ),
+            &parser_state,
+        );
     }
+
+    let red = analyser.state.get_team(Team::Red).unwrap();
+    assert_eq!("Froyotech", red.display_name());
+    assert_eq!(1, red.rounds_won);
+    assert_eq!(
+        vec![EntityId::from(4u32), EntityId::from(5u32)],
+        red.players
+    );
+    assert_eq!(
+        "BLU",
+        analyser.state.get_team(Team::Blue).unwrap().display_name()
+    );
+    assert_eq!(
+        Some("Froyotech 3 - 2 BLU".to_string()),
+        analyser.state.score_line()
+    );
+}
+
+#[test]
+fn test_condition_changes() {
+    use crate::demo::data::game_state::PlayerCondition;
+
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+    let player = EntityId::from(3u32);
+
+    analyser.tick = DemoTick::from(10u32);
+    analyser.handle_player_entity(
+        &test_entity(
+            3,
+            vec![
+                (
+                    "DT_TFPlayerShared",
+                    "m_nPlayerCond",
+                    SendPropValue::Integer((1 << 5) | (1 << 22)),
+                ),
+                (
+                    "DT_TFPlayerShared",
+                    "m_nPlayerCondEx",
+                    SendPropValue::Integer(0),
+                ),
+            ],
+        ),
+        &parser_state,
+    );
+    let conditions = analyser.state.get_player(player).unwrap().conditions;
+    assert!(conditions.is_ubered());
+    assert!(conditions.is_burning());
+    assert!(!conditions.is_cloaked());
+
+    analyser.tick = DemoTick::from(20u32);
+    analyser.handle_player_entity(
+        &test_entity(
+            3,
+            vec![
+                (
+                    "DT_TFPlayerConditionListExclusive",
+                    "_condition_bits",
+                    SendPropValue::Integer(1 << 5),
+                ),
+                // speed boost, condition 32
+                (
+                    "DT_TFPlayerShared",
+                    "m_nPlayerCondEx",
+                    SendPropValue::Integer(1),
+                ),
+            ],
+        ),
+        &parser_state,
+    );
+    let conditions = analyser.state.get_player(player).unwrap().conditions;
+    assert!(conditions.is_ubered());
+    assert!(!conditions.is_burning());
+    assert!(conditions.contains(PlayerCondition::SpeedBoost));
+
+    assert_eq!(4, analyser.state.condition_changes.len());
+    assert_eq!(
+        vec![(DemoTick::from(10u32), Some(DemoTick::from(20u32)))],
+        analyser
+            .state
+            .condition_periods(player, PlayerCondition::Burning)
+    );
+    assert_eq!(
+        vec![(DemoTick::from(10u32), None)],
+        analyser
+            .state
+            .condition_periods(player, PlayerCondition::Invulnerable)
+    );
+
+    // mannpower conditions are in the last two words
+    analyser.tick = DemoTick::from(30u32);
+    analyser.handle_player_entity(
+        &test_entity(
+            3,
+            vec![
+                (
+                    "DT_TFPlayerShared",
+                    "m_nPlayerCondEx2",
+                    SendPropValue::Integer(1 << (90 - 64)),
+                ),
+                (
+                    "DT_TFPlayerShared",
+                    "m_nPlayerCondEx3",
+                    SendPropValue::Integer((1 << (98 - 96)) | (1 << (105 - 96))),
+                ),
+            ],
+        ),
+        &parser_state,
+    );
+    let conditions = analyser.state.get_player(player).unwrap().conditions;
+    assert!(conditions.contains(PlayerCondition::RuneStrength));
+    assert!(conditions.contains(PlayerCondition::GrapplingHook));
+    assert!(conditions.is_crit_boosted());
+    assert_eq!(7, analyser.state.condition_changes.len());
+}
+
+#[test]
+fn test_weapon_switches() {
+    let parser_state = ParserState::new(24, |_| false, false);
+    let mut analyser = GameStateAnalyser::new();
+    let player = EntityId::from(1u32);
+    analyser.class_names = vec!["CTFScatterGun".into(), "CTFJarMilk".into(), "CTFBat".into()];
+
+    for (index, class, item_index) in [(644, 0u16, 200), (645, 1, 222), (646, 2, 190)] {
+        let mut entity = test_entity(
+            index,
+            vec![
+                (
+                    "DT_ScriptCreatedItem",
+                    "m_iItemDefinitionIndex",
+                    SendPropValue::Integer(item_index),
+                ),
+                ("DT_LocalWeaponData", "m_iClip1", SendPropValue::Integer(-1)),
+                (
+                    "DT_LocalWeaponData",
+                    "m_iPrimaryAmmoType",
+                    SendPropValue::Integer(if class == 0 { 1 } else { -1 }),
+                ),
+            ],
+        );
+        entity.server_class = class.into();
+        analyser.handle_weapon_entity(&entity, &parser_state);
+    }
+
+    for handle in [1606276, 1587845, 1606278].map(Handle::from) {
+        let entity = handle.index().unwrap();
+        analyser
+            .state
+            .entity_serials
+            .insert(entity, handle.serial());
+    }
+
+    for (tick, weapon) in [(10u32, 1606276), (20u32, 1587845)] {
+        analyser.tick = DemoTick::from(tick);
+        analyser.handle_player_entity(
+            &test_entity(
+                1,
+                vec![
+                    // the melee weapon was received after the other weapons
+                    ("m_hMyWeapons", "000", SendPropValue::Integer(1587845)),
+                    ("m_hMyWeapons", "001", SendPropValue::Integer(1606276)),
+                    ("m_hMyWeapons", "003", SendPropValue::Integer(1606278)),
+                    // slots without a weapon are networked as invalid handle
+                    ("m_hMyWeapons", "004", SendPropValue::Integer(2097151)),
+                    ("m_iAmmo", "001", SendPropValue::Integer(32)),
+                    (
+                        "DT_BaseCombatCharacter",
+                        "m_hActiveWeapon",
+                        SendPropValue::Integer(weapon),
+                    ),
+                ],
+            ),
+            &parser_state,
+        );
+        analyser.resolve_weapon_switches();
+    }
+
+    let state = &analyser.state;
+    let player_state = state.get_player(player).unwrap();
+    let loadout = state.loadout(player_state);
+    assert_eq!(3, loadout.weapons.len());
+    assert_eq!(Some(WeaponSlot::Secondary), loadout.active_slot());
+    assert_eq!(222, loadout.active.unwrap().item_index);
+    let primary = loadout.weapon(WeaponSlot::Primary).unwrap();
+    assert_eq!(200, primary.item_index);
+    assert_eq!(None, primary.clip);
+    assert_eq!(Some(32), player_state.reserve_ammo(primary));
+    assert_eq!(190, loadout.weapon(WeaponSlot::Melee).unwrap().item_index);
+    assert!(loadout.weapon(WeaponSlot::Pda).is_none());
+
+    assert_eq!(2, state.weapon_switches.len());
+    assert_eq!(
+        Some(200),
+        state
+            .active_weapon_at(player, DemoTick::from(15u32))
+            .and_then(|switch| switch.item_index)
+    );
+    assert_eq!(None, state.active_weapon_at(player, DemoTick::from(5u32)));
+    assert_eq!(3, player_state.weapons.len());
+
+    // the player loses a weapon
+    analyser.handle_player_entity(
+        &test_entity(
+            1,
+            vec![("m_hMyWeapons", "003", SendPropValue::Integer(2097151))],
+        ),
+        &parser_state,
+    );
+    let state = &analyser.state;
+    let loadout = state.loadout(state.get_player(player).unwrap());
+    assert_eq!(2, loadout.weapons.len());
+    let removed = Handle::from(1606278).index();
+    assert!(loadout
+        .weapons
+        .iter()
+        .all(|weapon| Some(weapon.entity) != removed));
 }
@@ -1,4 +1,4 @@
-use crate::demo::message::{Message, MessageType};
+use crate::demo::message::{Message, MessageType, ServerInfoMessage};
 use crate::demo::packet::datatable::{ParseSendTable, ServerClass};
 use crate::demo::packet::stringtable::{StringTable, StringTableEntry};
 use crate::demo::packet::Packet;
@@ -45,6 +45,18 @@ pub trait MessageHandler {
     ) {
     }
 
+    /// Called when a new signon sequence starts because the server changed map or reloaded
+    ///
+    /// The map specific parser state has been reset at this point, the new server info is handled
+    /// as a normal message after this.
+    fn handle_map_change(
+        &mut self,
+        _server_info: &ServerInfoMessage,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+    }
+
     fn into_output(self, state: &ParserState) -> Self::Output;
 }
 
@@ -69,6 +81,8 @@ pub struct DemoHandler<'a, T: MessageHandler> {
     pub server_tick: ServerTick,
     pub demo_tick: DemoTick,
     pub string_table_names: Vec<Cow<'a, str>>,
+    // a new server info after the first one marks a new signon sequence
+    seen_server_info: bool,
     analyser: T,
     pub state_handler: ParserState,
 }
@@ -93,6 +107,7 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
             server_tick: ServerTick::default(),
             demo_tick: DemoTick::default(),
             string_table_names: Vec::new(),
+            seen_server_info: false,
             analyser,
             state_handler,
         }
@@ -104,6 +119,7 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
             server_tick: ServerTick::default(),
             demo_tick: DemoTick::default(),
             string_table_names: Vec::new(),
+            seen_server_info: false,
             analyser,
             state_handler,
         }
@@ -115,6 +131,7 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
     }
 
     pub fn handle_packet(&mut self, packet: Packet<'a>) -> Result<()> {
+        self.state_handler.tick = packet.tick();
         match packet {
             Packet::DataTables(packet) => {
                 self.handle_data_table(packet.tables, packet.server_classes)?;
@@ -133,6 +150,13 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
                             self.server_tick = message.tick;
                             self.handle_message(Message::NetTick(message), packet.tick)
                         }
+                        Message::ServerInfo(message) => {
+                            if self.seen_server_info {
+                                self.handle_map_change(&message, packet.tick);
+                            }
+                            self.seen_server_info = true;
+                            self.handle_message(Message::ServerInfo(message), packet.tick)
+                        }
                         Message::CreateStringTable(message) => {
                             self.handle_string_table(message.table)
                         }
@@ -181,6 +205,13 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
         }
     }
 
+    fn handle_map_change(&mut self, server_info: &ServerInfoMessage, tick: DemoTick) {
+        self.state_handler.reset_map_state();
+        self.string_table_names.clear();
+        self.analyser
+            .handle_map_change(server_info, tick, &self.state_handler);
+    }
+
     fn handle_data_table(
         &mut self,
         send_tables: Vec<ParseSendTable>,
@@ -215,3 +246,85 @@ impl<T: MessageHandler + BorrowMessageHandler> DemoHandler<'_, T> {
         self.analyser.borrow_output(&self.state_handler)
     }
 }
+
+#[test]
+fn test_map_change() {
+    use crate::demo::data::stringtables::MODEL_PRECACHE;
+    use crate::demo::packet::message::MessagePacket;
+
+    #[derive(Default)]
+    struct MapChanges(Vec<String>);
+
+    impl MessageHandler for MapChanges {
+        type Output = Vec<String>;
+
+        fn does_handle(_message_type: MessageType) -> bool {
+            false
+        }
+
+        fn handle_map_change(
+            &mut self,
+            server_info: &ServerInfoMessage,
+            _tick: DemoTick,
+            _parser_state: &ParserState,
+        ) {
+            self.0.push(server_info.map.clone());
+        }
+
+        fn into_output(self, _state: &ParserState) -> Self::Output {
+            self.0
+        }
+    }
+
+    fn server_info(map: &str) -> Packet<'static> {
+        Packet::Signon(MessagePacket {
+            tick: DemoTick::default(),
+            messages: vec![Message::ServerInfo(Box::new(ServerInfoMessage {
+                version: 24,
+                server_count: 1,
+                stv: true,
+                dedicated: true,
+                max_crc: 0,
+                max_classes: 0,
+                map_hash: [0; 16],
+                player_slot: 0,
+                max_player_count: 24,
+                interval_per_tick: 0.015,
+                platform: "l".into(),
+                game: "tf".into(),
+                map: map.into(),
+                skybox: "sky_tf2_04".into(),
+                server_name: "server".into(),
+                replay: false,
+            }))],
+            meta: MessagePacketMeta::default(),
+        })
+    }
+
+    let mut handler = DemoHandler::with_analyser(MapChanges::default());
+    handler
+        .handle_packet(server_info("cp_process_final"))
+        .unwrap();
+    handler.state_handler.strings.insert(
+        MODEL_PRECACHE,
+        1,
+        Some("maps/cp_process_final.bsp"),
+        0u32.into(),
+    );
+    handler.string_table_names.push("modelprecache".into());
+    handler
+        .state_handler
+        .entity_classes
+        .insert(10u32.into(), 3u16.into());
+
+    handler
+        .handle_packet(server_info("koth_product_final"))
+        .unwrap();
+    assert_eq!(None, handler.state_handler.strings.model_name(1));
+    assert!(handler.string_table_names.is_empty());
+    assert!(handler.state_handler.entity_classes.is_empty());
+    assert_eq!(
+        vec!["koth_product_final".to_string()],
+        handler.into_output()
+    );
+}
@@ -0,0 +1,522 @@
+use crate::demo::data::game_state::{MedigunType, PlayerState};
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::PlayerChargeDeployedEvent;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::{Message, MessageType, ServerInfoMessage};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass};
+use crate::demo::packet::message::MessagePacketMeta;
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::{Class, Team, UserId};
+use crate::demo::parser::gamestateanalyser::GameStateAnalyser;
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Deaths with at least this much charge, but without a full charge, count as near full deaths
+const NEAR_FULL_CHARGE: f32 = 0.95;
+
+/// Collects uber builds, uses and drops for every medic life and the uber advantage between the teams
+///
+/// The medigun state is tracked by an inner [`GameStateAnalyser`].
+#[derive(Default, Debug)]
+pub struct MedicAnalyser {
+    game_state: GameStateAnalyser,
+    state: MedicState,
+    // last seen charge release state for every medic, to detect the end of an uber
+    charge_release: HashMap<UserId, bool>,
+    // last charge of every medic while alive, the medigun can already be reset or removed by the
+    // entity updates of the packet that contains the death
+    alive_charge: HashMap<UserId, f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MedicState {
+    pub medics: BTreeMap<UserId, Medic>,
+    /// The charge of both teams, recorded every time one of them changes
+    pub advantage: Vec<UberAdvantage>,
+    pub interval_per_tick: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Medic {
+    pub user_id: UserId,
+    pub name: String,
+    pub team: Team,
+    pub lives: Vec<MedicLife>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MedicLife {
+    pub start: DemoTick,
+    pub end: Option<DemoTick>,
+    /// The first tick of the life with a full charge
+    pub full_charge: Option<DemoTick>,
+    pub ubers: Vec<Uber>,
+    /// The charge at the moment of death, `None` if the life didn't end in a death
+    pub death_charge: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Uber {
+    pub start: DemoTick,
+    pub end: Option<DemoTick>,
+    pub medigun: MedigunType,
+    pub target: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UberAdvantage {
+    pub tick: DemoTick,
+    /// Charge percentage of the red medic
+    pub red: u8,
+    /// Charge percentage of the blue medic
+    pub blue: u8,
+}
+
+impl UberAdvantage {
+    /// The charge advantage of red over blue in percent, negative when blue is ahead
+    pub fn advantage(&self) -> i16 {
+        self.red as i16 - self.blue as i16
+    }
+}
+
+impl Uber {
+    /// Length of the uber in ticks
+    pub fn length(&self) -> Option<u32> {
+        Some(u32::from(self.end?).saturating_sub(u32::from(self.start)))
+    }
+}
+
+impl MedicLife {
+    fn new(start: DemoTick) -> Self {
+        MedicLife {
+            start,
+            ..MedicLife::default()
+        }
+    }
+
+    /// Ticks from the start of the life until the first full charge
+    pub fn build_time(&self) -> Option<u32> {
+        Some(u32::from(self.full_charge?).saturating_sub(u32::from(self.start)))
+    }
+
+    /// Whether the medic died with a full charge
+    pub fn is_drop(&self) -> bool {
+        matches!(self.death_charge, Some(charge) if charge >= 1.0)
+    }
+
+    /// Whether the medic died with an almost full charge
+    pub fn is_near_full_death(&self) -> bool {
+        matches!(self.death_charge, Some(charge) if (NEAR_FULL_CHARGE..1.0).contains(&charge))
+    }
+
+    fn active_uber(&mut self) -> Option<&mut Uber> {
+        self.ubers.last_mut().filter(|uber| uber.end.is_none())
+    }
+
+    fn finish(&mut self, tick: DemoTick) {
+        self.end = Some(tick);
+        if let Some(uber) = self.active_uber() {
+            uber.end = Some(tick);
+        }
+    }
+}
+
+impl Medic {
+    fn current_life(&mut self) -> Option<&mut MedicLife> {
+        self.lives.last_mut().filter(|life| life.end.is_none())
+    }
+
+    pub fn ubers_used(&self) -> usize {
+        self.lives.iter().map(|life| life.ubers.len()).sum()
+    }
+
+    pub fn drops(&self) -> usize {
+        self.lives.iter().filter(|life| life.is_drop()).count()
+    }
+
+    pub fn near_full_deaths(&self) -> usize {
+        self.lives
+            .iter()
+            .filter(|life| life.is_near_full_death())
+            .count()
+    }
+
+    /// Average number of ticks needed to build a full charge, for the lives where one was built
+    pub fn average_build_time(&self) -> Option<f32> {
+        average(self.lives.iter().filter_map(MedicLife::build_time))
+    }
+
+    /// Average length of the finished ubers in ticks
+    pub fn average_uber_length(&self) -> Option<f32> {
+        average(
+            self.lives
+                .iter()
+                .flat_map(|life| life.ubers.iter())
+                .filter_map(Uber::length),
+        )
+    }
+}
+
+fn average(values: impl Iterator<Item = u32>) -> Option<f32> {
+    let (count, total) = values.fold((0u32, 0u64), |(count, total), value| {
+        (count + 1, total + value as u64)
+    });
+    (count > 0).then(|| total as f32 / count as f32)
+}
+
+impl MessageHandler for MedicAnalyser {
+    type Output = MedicState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        GameStateAnalyser::does_handle(message_type)
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.game_state.handle_message(message, tick, parser_state);
+
+        match message {
+            Message::PacketEntities(_) => self.update_charges(tick),
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick
+            }
+            Message::GameEvent(GameEventMessage { event, .. }) => match event {
+                GameEvent::PlayerSpawn(spawn) => {
+                    self.handle_spawn(UserId::from(spawn.user_id), Class::new(spawn.class), tick)
+                }
+                GameEvent::PlayerChargeDeployed(event) => self.handle_charge_deployed(event, tick),
+                GameEvent::PlayerDeath(death) => {
+                    self.handle_death(UserId::from(death.user_id), tick)
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        self.game_state
+            .handle_string_entry(table, index, entry, parser_state);
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        parser_state: &ParserState,
+    ) {
+        self.game_state
+            .handle_data_tables(parse_tables, server_classes, parser_state);
+    }
+
+    fn handle_packet_meta(
+        &mut self,
+        tick: DemoTick,
+        meta: &MessagePacketMeta,
+        parser_state: &ParserState,
+    ) {
+        self.game_state.handle_packet_meta(tick, meta, parser_state);
+    }
+
+    fn handle_map_change(
+        &mut self,
+        server_info: &ServerInfoMessage,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        self.game_state
+            .handle_map_change(server_info, tick, parser_state);
+        self.charge_release.clear();
+        self.alive_charge.clear();
+        for medic in self.state.medics.values_mut() {
+            if let Some(life) = medic.current_life() {
+                life.finish(tick);
+            }
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for MedicAnalyser {
+    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+        &self.state
+    }
+}
+
+impl MedicAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn medic(&mut self, user_id: UserId) -> &mut Medic {
+        let player = self
+            .game_state
+            .state
+            .players
+            .iter()
+            .filter_map(|player| player.info.as_ref())
+            .find(|info| info.user_id == user_id);
+        let medic = self.state.medics.entry(user_id).or_insert_with(|| Medic {
+            user_id,
+            ..Medic::default()
+        });
+        if let Some(info) = player {
+            medic.name.clone_from(&info.name);
+        }
+        medic
+    }
+
+    /// The current charge of a medic and whether the uber is active
+    fn charge(&self, user_id: UserId) -> Option<(f32, bool)> {
+        let state = &self.game_state.state;
+        let player = state
+            .players
+            .iter()
+            .find(|player| player.info.as_ref().map(|info| info.user_id) == Some(user_id))?;
+        let medigun = state.get_medigun(player)?;
+        Some((medigun.charge, medigun.charge_release))
+    }
+
+    fn handle_spawn(&mut self, user_id: UserId, class: Class, tick: DemoTick) {
+        if class != Class::Medic {
+            if let Some(life) = self
+                .state
+                .medics
+                .get_mut(&user_id)
+                .and_then(Medic::current_life)
+            {
+                life.finish(tick);
+            }
+            return;
+        }
+
+        let medic = self.medic(user_id);
+        match medic.current_life() {
+            // the life was already started when the medic was first seen this tick
+            Some(life) if life.start == tick => {}
+            Some(life) => {
+                life.finish(tick);
+                medic.lives.push(MedicLife::new(tick));
+            }
+            None => medic.lives.push(MedicLife::new(tick)),
+        }
+    }
+
+    fn handle_charge_deployed(&mut self, event: &PlayerChargeDeployedEvent, tick: DemoTick) {
+        let user_id = UserId::from(event.user_id);
+        let medigun = self
+            .game_state
+            .state
+            .players
+            .iter()
+            .find(|player| player.info.as_ref().map(|info| info.user_id) == Some(user_id))
+            .and_then(|player| self.game_state.state.get_medigun(player))
+            .map(|medigun| medigun.ty)
+            .unwrap_or_default();
+
+        let medic = self.medic(user_id);
+        if medic.current_life().is_none() {
+            medic.lives.push(MedicLife::new(tick));
+        }
+        if let Some(life) = medic.current_life() {
+            life.ubers.push(Uber {
+                start: tick,
+                end: None,
+                medigun,
+                target: UserId::from(event.target_id),
+            });
+        }
+    }
+
+    fn handle_death(&mut self, user_id: UserId, tick: DemoTick) {
+        let charge = self
+            .alive_charge
+            .remove(&user_id)
+            .or_else(|| self.charge(user_id).map(|(charge, _)| charge));
+        if let Some(life) = self
+            .state
+            .medics
+            .get_mut(&user_id)
+            .and_then(Medic::current_life)
+        {
+            life.finish(tick);
+            life.death_charge = charge;
+        }
+        self.charge_release.remove(&user_id);
+    }
+
+    fn update_charges(&mut self, tick: DemoTick) {
+        let medics: Vec<(UserId, Team, PlayerState)> = self
+            .game_state
+            .state
+            .players
+            .iter()
+            .filter(|player| player.class == Class::Medic)
+            .filter_map(|player| Some((player.info.as_ref()?.user_id, player.team, player.state)))
+            .collect();
+
+        let mut red = None;
+        let mut blue = None;
+
+        for (user_id, team, player_state) in medics {
+            let Some((charge, release)) = self.charge(user_id) else {
+                continue;
+            };
+            if player_state != PlayerState::Alive {
+                continue;
+            }
+            self.alive_charge.insert(user_id, charge);
+
+            let was_releasing = self.charge_release.insert(user_id, release);
+            let medic = self.medic(user_id);
+            medic.team = team;
+            if medic.current_life().is_none() {
+                // medics that were already alive at the start of the demo
+                medic.lives.push(MedicLife::new(tick));
+            }
+            if let Some(life) = medic.current_life() {
+                if charge >= 1.0 && life.full_charge.is_none() {
+                    life.full_charge = Some(tick);
+                }
+                if was_releasing == Some(true) && !release {
+                    if let Some(uber) = life.active_uber() {
+                        uber.end = Some(tick);
+                    }
+                }
+            }
+
+            let percent = (charge * 100.0).round().clamp(0.0, 100.0) as u8;
+            let team_charge = match team {
+                Team::Red => &mut red,
+                Team::Blue => &mut blue,
+                _ => continue,
+            };
+            *team_charge = Some(team_charge.map_or(percent, |other: u8| other.max(percent)));
+        }
+
+        if red.is_none() && blue.is_none() {
+            return;
+        }
+        let (red, blue) = (red.unwrap_or_default(), blue.unwrap_or_default());
+        let changed = self
+            .state
+            .advantage
+            .last()
+            .map_or(true, |last| last.red != red || last.blue != blue);
+        if changed {
+            self.state.advantage.push(UberAdvantage { tick, red, blue });
+        }
+    }
+}
+
+#[test]
+fn test_medic_lives() {
+    use crate::demo::data::game_state::{Handle, Medigun};
+    use crate::demo::message::packetentities::EntityId;
+
+    let mut analyser = MedicAnalyser::new();
+    let user_id = UserId::from(7u16);
+    let state = &mut analyser.game_state.state;
+    let player = state.get_or_create_player(EntityId::from(2u32));
+    player.class = Class::Medic;
+    player.team = Team::Blue;
+    player.weapons = [(1, Handle::new(EntityId::from(40u32), 3))].into();
+    player.info = Some(
+        crate::demo::data::UserInfo {
+            entity_id: 2u32.into(),
+            player_info: crate::demo::data::userinfo::PlayerInfo {
+                name: "medic".into(),
+                user_id,
+                ..Default::default()
+            },
+        }
+        .into(),
+    );
+    state.entity_serials.insert(EntityId::from(40u32), 3);
+    state.mediguns.insert(
+        EntityId::from(40u32),
+        Medigun {
+            ty: MedigunType::Kritzkrieg,
+            ..Medigun::new(EntityId::from(40u32))
+        },
+    );
+
+    let set_charge = |analyser: &mut MedicAnalyser, charge: f32, release: bool| {
+        let medigun = analyser
+            .game_state
+            .state
+            .mediguns
+            .get_mut(&EntityId::from(40u32))
+            .unwrap();
+        medigun.charge = charge;
+        medigun.charge_release = release;
+    };
+
+    analyser.handle_spawn(user_id, Class::Medic, DemoTick::from(100u32));
+    set_charge(&mut analyser, 0.5, false);
+    analyser.update_charges(DemoTick::from(200u32));
+    set_charge(&mut analyser, 1.0, false);
+    analyser.update_charges(DemoTick::from(300u32));
+    analyser.handle_charge_deployed(
+        &PlayerChargeDeployedEvent {
+            user_id: 7,
+            target_id: 3,
+        },
+        DemoTick::from(310u32),
+    );
+    set_charge(&mut analyser, 0.9, true);
+    analyser.update_charges(DemoTick::from(320u32));
+    set_charge(&mut analyser, 0.0, false);
+    analyser.update_charges(DemoTick::from(700u32));
+
+    set_charge(&mut analyser, 1.0, false);
+    analyser.update_charges(DemoTick::from(900u32));
+    analyser.handle_death(user_id, DemoTick::from(950u32));
+
+    analyser.handle_spawn(user_id, Class::Medic, DemoTick::from(1000u32));
+    set_charge(&mut analyser, 0.97, false);
+    analyser.update_charges(DemoTick::from(1500u32));
+    // the medigun is reset by the same entity update that kills the medic
+    set_charge(&mut analyser, 0.0, false);
+    analyser.game_state.state.players[0].state = PlayerState::Dying;
+    analyser.update_charges(DemoTick::from(1600u32));
+    analyser.handle_death(user_id, DemoTick::from(1600u32));
+
+    let medic = &analyser.state.medics[&user_id];
+    assert_eq!("medic", medic.name);
+    assert_eq!(Team::Blue, medic.team);
+    assert_eq!(2, medic.lives.len());
+    assert_eq!(Some(200), medic.lives[0].build_time());
+    assert_eq!(1, medic.ubers_used());
+    assert_eq!(MedigunType::Kritzkrieg, medic.lives[0].ubers[0].medigun);
+    assert_eq!(UserId::from(3u16), medic.lives[0].ubers[0].target);
+    assert_eq!(Some(390.0), medic.average_uber_length());
+    assert_eq!(1, medic.drops());
+    assert_eq!(1, medic.near_full_deaths());
+    assert_eq!(Some(DemoTick::from(1600u32)), medic.lives[1].end);
+    assert_eq!(None, medic.lives[1].build_time());
+
+    let advantage: Vec<i16> = analyser
+        .state
+        .advantage
+        .iter()
+        .map(UberAdvantage::advantage)
+        .collect();
+    assert_eq!(vec![-50, -100, -90, 0, -100, -97], advantage);
+}
@@ -13,12 +13,16 @@ pub use crate::demo::parser::state::ParserState;
 use crate::Stream;
 
 pub mod analyser;
+pub mod decalanalyser;
 pub mod error;
 pub mod gamestateanalyser;
 pub mod handler;
+pub mod medicanalyser;
 pub mod messagetypeanalyser;
 pub mod player_summary_analyzer;
 pub mod state;
+pub mod voiceanalyser;
+pub mod voteanalyser;
 
 pub use self::error::*;
 use crate::demo::parser::handler::BorrowMessageHandler;
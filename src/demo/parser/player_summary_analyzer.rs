@@ -1,3 +1,4 @@
+use crate::demo::data::game_state::{score_line, TeamState};
 use crate::demo::data::DemoTick;
 use crate::demo::message::packetentities::EntityId;
 use crate::demo::message::packetentities::PacketEntity;
@@ -5,10 +6,10 @@ use crate::demo::message::{Message, MessageType};
 use crate::demo::packet::datatable::ClassId;
 use crate::demo::packet::stringtable::StringTableEntry;
 use crate::demo::parser::analyser::UserInfo;
-use crate::demo::parser::gamestateanalyser::UserId;
+use crate::demo::parser::gamestateanalyser::{update_team_state, UserId};
 use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
 use crate::demo::sendprop::SendProp;
-use crate::{ParserState, ReadResult, Stream};
+use crate::ParserState;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 
@@ -49,6 +50,14 @@ pub struct PlayerSummary {
 pub struct PlayerSummaryState {
     pub player_summaries: HashMap<UserId, PlayerSummary>,
     pub users: BTreeMap<UserId, UserInfo>,
+    pub teams: Vec<TeamState>,
+}
+
+impl PlayerSummaryState {
+    /// The final score as `RED 3 - 2 BLU`
+    pub fn score_line(&self) -> Option<String> {
+        score_line(&self.teams)
+    }
 }
 
 impl MessageHandler for PlayerSummaryAnalyzer {
@@ -77,12 +86,10 @@ impl MessageHandler for PlayerSummaryAnalyzer {
         entry: &StringTableEntry,
         _parser_state: &ParserState,
     ) {
-        if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+        if let Some((_, user_info)) =
+            crate::demo::data::UserInfo::from_string_entry(table, index, entry)
+        {
+            user_info.update_users(&mut self.state.users);
         }
     }
 }
@@ -374,31 +381,11 @@ impl PlayerSummaryAnalyzer {
                         }
                     }
                 }
+                "CTFTeam" => update_team_state(&mut self.state.teams, packet, parser_state),
                 _other => {
                     // Don't care
                 }
             }
         }
     }
-
-    fn parse_user_info(
-        &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            self.state
-                .users
-                .entry(user_info.player_info.user_id)
-                .and_modify(|info| {
-                    info.entity_id = user_info.entity_id;
-                })
-                .or_insert_with(|| user_info.into());
-        }
-
-        Ok(())
-    }
 }
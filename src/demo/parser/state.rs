@@ -14,6 +14,7 @@ use crate::demo::packet::datatable::{
 };
 use crate::demo::packet::stringtable::StringTableEntry;
 
+use crate::demo::data::stringtables::{StringTables, PRECACHE_TABLES};
 use crate::demo::data::DemoTick;
 use crate::demo::sendprop::{SendProp, SendPropIdentifier};
 use crate::nullhasher::NullHasherBuilder;
@@ -42,6 +43,10 @@ pub struct ParserState {
     pub server_classes: Vec<ServerClass>,
     pub instance_baselines: [Baseline; 2],
     pub demo_meta: DemoMeta,
+    /// The text of the entries of the precache tables
+    pub strings: StringTables,
+    /// Tick of the packet that is being handled
+    pub tick: DemoTick,
     analyser_handles: fn(message_type: MessageType) -> bool,
     handle_entities: bool,
     parse_all: bool,
@@ -87,6 +92,8 @@ impl<'a> ParserState {
             server_classes: Vec::new(),
             instance_baselines: [Baseline::default(), Baseline::default()],
             demo_meta: DemoMeta::default(),
+            strings: StringTables::default(),
+            tick: DemoTick::default(),
             analyser_handles,
             handle_entities: analyser_handles(MessageType::PacketEntities) || parse_all,
             parse_all,
@@ -195,6 +202,7 @@ impl<'a> ParserState {
 
             self.server_classes = server_classes;
 
+            self.send_tables.clear();
             self.send_tables.reserve(self.server_classes.len());
 
             for class in self.server_classes.iter() {
@@ -210,6 +218,19 @@ impl<'a> ParserState {
         Ok(())
     }
 
+    /// Clear all state that is specific to the current map, called when a new signon sequence
+    /// starts after a map change or a server reload
+    ///
+    /// Send tables, server classes and event definitions are kept until the server sends new ones.
+    pub fn reset_map_state(&mut self) {
+        self.static_baselines.clear();
+        self.parsed_static_baselines.borrow_mut().clear();
+        self.string_tables.clear();
+        self.entity_classes.clear();
+        self.instance_baselines = [Baseline::default(), Baseline::default()];
+        self.strings = StringTables::default();
+    }
+
     pub fn handle_string_table_meta(&mut self, table: StringTableMeta) {
         self.string_tables.push(table);
     }
@@ -294,12 +315,11 @@ impl<'a> ParserState {
         }
     }
 
-    pub fn handle_string_entry(
-        &mut self,
-        table: &str,
-        _index: usize,
-        entry: &StringTableEntry<'a>,
-    ) {
+    pub fn handle_string_entry(&mut self, table: &str, index: usize, entry: &StringTableEntry<'a>) {
+        if PRECACHE_TABLES.contains(&table) {
+            self.strings
+                .insert(table, index, entry.text.as_deref(), self.tick);
+        }
         if table == "instancebaseline" {
             if let (Some(extra), Ok(class_id)) = (&entry.extra_data, entry.text().parse()) {
                 let baseline = StaticBaseline::new(class_id, extra.data.to_owned());
@@ -423,3 +443,20 @@ impl From<BaselineEntity> for PacketEntity {
         }
     }
 }
+
+#[test]
+fn test_keep_precache_strings() {
+    use crate::demo::data::stringtables::SOUND_PRECACHE;
+
+    let mut state = ParserState::new(24, |_| false, false);
+    let entry = |text: &'static str| StringTableEntry {
+        text: Some(text.into()),
+        extra_data: None,
+    };
+    state.handle_string_entry(SOUND_PRECACHE, 2, &entry("Weapon_RPG.Single"));
+    state.handle_string_entry("downloadables", 0, &entry("maps/cp_process_final.bsp"));
+
+    assert_eq!(Some("Weapon_RPG.Single"), state.strings.sound_name(2));
+    assert!(state.strings.get("downloadables").is_none());
+    assert_eq!(1, state.strings.tables().count());
+}
@@ -0,0 +1,354 @@
+use crate::demo::data::{DemoTick, UserInfo};
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::message::voice::{SteamVoicePacket, VoicePayload};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::ogg::{
+    opus_packet_samples, write_opus_headers, OggWriter, OPUS_SAMPLE_RATE, OPUS_SILENCE_PACKET,
+    OPUS_SILENCE_SAMPLES,
+};
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::UserId;
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Sample rate used by steam voice when no sample rate is sent
+const DEFAULT_SAMPLE_RATE: u16 = 24000;
+
+/// Collects the voice data sent by every player
+///
+/// Every player gets its own speaker track, a new track is started when a different player takes
+/// over the slot of a previous speaker.
+#[derive(Default, Debug)]
+pub struct VoiceAnalyser {
+    state: VoiceState,
+    // the user currently in each player slot
+    users: HashMap<EntityId, UserId>,
+    // index of the current speaker track for each player slot
+    tracks: HashMap<EntityId, usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceState {
+    pub codec: String,
+    pub quality: u8,
+    pub sampling_rate: u16,
+    pub interval_per_tick: f32,
+    /// The speakers, in the order they started speaking
+    pub speakers: Vec<Speaker>,
+}
+
+/// All voice data sent by a single player
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Speaker {
+    pub entity_id: EntityId,
+    /// The user id of the speaking player, if the player was known when they started speaking
+    pub user_id: Option<UserId>,
+    pub frames: Vec<VoiceFrame>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceFrame {
+    pub tick: DemoTick,
+    pub proximity: bool,
+    pub data: Vec<u8>,
+}
+
+impl VoiceFrame {
+    /// Parse the frame as steam voice packet, only valid when the voice codec is `"steam"`
+    pub fn steam_packet(&self) -> Option<SteamVoicePacket> {
+        SteamVoicePacket::parse(&self.data).ok()
+    }
+}
+
+impl Speaker {
+    pub fn steam_id(&self) -> Option<u64> {
+        self.frames
+            .iter()
+            .find_map(|frame| frame.steam_packet())
+            .map(|packet| packet.steam_id)
+    }
+
+    /// The number of SILK payloads in the steam voice packets, these can't be written to ogg
+    pub fn silk_payloads(&self) -> usize {
+        self.frames
+            .iter()
+            .filter_map(|frame| frame.steam_packet())
+            .flat_map(|packet| packet.payloads)
+            .filter(|payload| matches!(payload, VoicePayload::Silk(_)))
+            .count()
+    }
+
+    /// Write the opus frames from the steam voice packets into an ogg file
+    ///
+    /// Silence is inserted between the frames to line the audio up with the demo, the start of the
+    /// file lines up with the start of the demo. SILK payloads are skipped, see
+    /// [`silk_payloads`](Self::silk_payloads).
+    pub fn write_ogg<W: Write>(&self, interval_per_tick: f32, writer: W) -> io::Result<W> {
+        let packets: Vec<(DemoTick, SteamVoicePacket)> = self
+            .frames
+            .iter()
+            .filter_map(|frame| Some((frame.tick, frame.steam_packet()?)))
+            .collect();
+        let mut sample_rate = packets
+            .iter()
+            .find_map(|(_, packet)| packet.sample_rate())
+            .unwrap_or(DEFAULT_SAMPLE_RATE);
+
+        let mut ogg = OggWriter::new(writer, u32::from(self.entity_id));
+        write_opus_headers(&mut ogg, sample_rate as u32)?;
+
+        let mut granule = 0u64;
+        for (tick, packet) in packets {
+            let start =
+                (u32::from(tick) as f64 * interval_per_tick as f64 * OPUS_SAMPLE_RATE as f64)
+                    .round() as u64;
+            granule = write_silence(&mut ogg, granule, start)?;
+
+            for payload in packet.payloads {
+                match payload {
+                    VoicePayload::SampleRate(rate) => sample_rate = rate,
+                    VoicePayload::Silence(samples) => {
+                        let samples =
+                            samples as u64 * OPUS_SAMPLE_RATE as u64 / sample_rate.max(1) as u64;
+                        granule = write_silence(&mut ogg, granule, granule + samples)?;
+                    }
+                    VoicePayload::Opus(frames) => {
+                        for frame in frames {
+                            if let Some(samples) = opus_packet_samples(&frame.data) {
+                                granule += samples as u64;
+                                ogg.write_packet(&frame.data, granule)?;
+                            }
+                        }
+                    }
+                    VoicePayload::Silk(_) => {}
+                }
+            }
+        }
+
+        ogg.finish()
+    }
+}
+
+/// Fill the stream with silence up to `target`, returns the new granule position
+fn write_silence<W: Write>(
+    ogg: &mut OggWriter<W>,
+    mut granule: u64,
+    target: u64,
+) -> io::Result<u64> {
+    while granule + OPUS_SILENCE_SAMPLES as u64 <= target {
+        granule += OPUS_SILENCE_SAMPLES as u64;
+        ogg.write_packet(&OPUS_SILENCE_PACKET, granule)?;
+    }
+    Ok(granule)
+}
+
+impl MessageHandler for VoiceAnalyser {
+    type Output = VoiceState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::VoiceInit | MessageType::VoiceData | MessageType::ServerInfo
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        match message {
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick
+            }
+            Message::VoiceInit(message) => {
+                self.state.codec = message.codec.clone();
+                self.state.quality = message.quality;
+                self.state.sampling_rate = message.sampling_rate;
+            }
+            Message::VoiceData(message) => {
+                let mut stream = message.data.clone();
+                let data = match stream.read_bytes(stream.bit_len() / 8) {
+                    Ok(data) => data.into_owned(),
+                    Err(_) => return,
+                };
+                let entity_id = message.entity_id();
+                let speakers = &mut self.state.speakers;
+                let track = *self.tracks.entry(entity_id).or_insert_with(|| {
+                    speakers.push(Speaker {
+                        entity_id,
+                        user_id: self.users.get(&entity_id).copied(),
+                        frames: Vec::new(),
+                    });
+                    speakers.len() - 1
+                });
+                if let Some(speaker) = speakers.get_mut(track) {
+                    speaker.frames.push(VoiceFrame {
+                        tick,
+                        proximity: message.proximity != 0,
+                        data,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        _parser_state: &ParserState,
+    ) {
+        if let Some((user_id, user_info)) = UserInfo::from_string_entry(table, index, entry) {
+            self.set_user(user_info.entity_id, user_id);
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for VoiceAnalyser {
+    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+        &self.state
+    }
+}
+
+impl VoiceAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set_user(&mut self, entity_id: EntityId, user_id: UserId) {
+        if self.users.insert(entity_id, user_id) != Some(user_id) {
+            // voice data from this slot belongs to a different player now
+            self.tracks.remove(&entity_id);
+        }
+    }
+}
+
+#[test]
+fn test_speaker_ogg_timing() {
+    fn packet(sequence: u16) -> Vec<u8> {
+        let mut data = 76561198000000000u64.to_le_bytes().to_vec();
+        data.extend_from_slice(&[11, 0xc0, 0x5d]);
+        // opus payload with two 20ms frames
+        data.extend_from_slice(&[6, 12, 0]);
+        data.extend_from_slice(&[2, 0]);
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(&[0x48, 1]);
+        data.extend_from_slice(&[2, 0]);
+        data.extend_from_slice(&(sequence + 1).to_le_bytes());
+        data.extend_from_slice(&[0x48, 2]);
+        data.extend_from_slice(&[0; 4]);
+        data
+    }
+
+    let speaker = Speaker {
+        entity_id: 3u32.into(),
+        user_id: None,
+        frames: vec![
+            VoiceFrame {
+                tick: 100u32.into(),
+                proximity: false,
+                data: packet(0),
+            },
+            VoiceFrame {
+                tick: 102u32.into(),
+                proximity: false,
+                data: packet(2),
+            },
+        ],
+    };
+    assert_eq!(Some(76561198000000000), speaker.steam_id());
+
+    let data = speaker.write_ogg(0.015, Vec::new()).unwrap();
+    let last_page = data
+        .windows(4)
+        .rposition(|window| window == b"OggS")
+        .unwrap();
+    let granule = u64::from_le_bytes(data[last_page + 6..last_page + 14].try_into().unwrap());
+    // 1.5s of silence up to the first tick, plus 4 frames of 20ms
+    assert_eq!(72000 + 4 * 960, granule);
+}
+
+#[test]
+fn test_speaker_per_user() {
+    use crate::demo::message::voice::VoiceDataMessage;
+    use bitbuffer::{BitReadBuffer, LittleEndian};
+
+    let state = ParserState::new(24, |_| false, false);
+    let voice = |client: u8| {
+        let data = [client; 4];
+        Message::VoiceData(VoiceDataMessage {
+            client,
+            proximity: 0,
+            length: 32,
+            data: crate::Stream::from(BitReadBuffer::new_owned(data.to_vec(), LittleEndian)),
+        })
+    };
+
+    let mut analyser = VoiceAnalyser::new();
+    analyser.set_user(3u32.into(), 10u16.into());
+    analyser.handle_message(&voice(2), 1u32.into(), &state);
+    analyser.handle_message(&voice(2), 2u32.into(), &state);
+    analyser.handle_message(&voice(4), 3u32.into(), &state);
+    // the same player again doesn't start a new track
+    analyser.set_user(3u32.into(), 10u16.into());
+    analyser.handle_message(&voice(2), 4u32.into(), &state);
+    // a different player in the same slot
+    analyser.set_user(3u32.into(), 11u16.into());
+    analyser.handle_message(&voice(2), 5u32.into(), &state);
+
+    let speakers = analyser.into_output(&state).speakers;
+    let tracks: Vec<_> = speakers
+        .iter()
+        .map(|speaker| {
+            (
+                speaker.entity_id,
+                speaker.user_id,
+                speaker.frames.iter().map(|frame| frame.tick).collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                EntityId::from(3u32),
+                Some(UserId::from(10u16)),
+                vec![1u32.into(), 2u32.into(), 4u32.into()]
+            ),
+            (EntityId::from(5u32), None, vec![3u32.into()]),
+            (
+                EntityId::from(3u32),
+                Some(UserId::from(11u16)),
+                vec![DemoTick::from(5u32)]
+            ),
+        ],
+        tracks
+    );
+}
+
+#[test]
+fn test_silk_payloads() {
+    let mut data = 76561198000000000u64.to_le_bytes().to_vec();
+    data.extend_from_slice(&[4, 2, 0, 1, 2]);
+    data.extend_from_slice(&[4, 1, 0, 3]);
+    data.extend_from_slice(&[0; 4]);
+
+    let speaker = Speaker {
+        entity_id: 3u32.into(),
+        user_id: None,
+        frames: vec![VoiceFrame {
+            tick: 1u32.into(),
+            proximity: false,
+            data,
+        }],
+    };
+    assert_eq!(2, speaker.silk_payloads());
+}
@@ -0,0 +1,316 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::{GameEvent, VoteCastEvent, VoteOptionsEvent};
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::message::usermessage::{
+    UserMessage, VoteFailedMessage, VotePassMessage, VoteStartMessage,
+};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::{Team, UserId, UserInfo};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Collects all votes called during the demo, together with the choices made by every player
+#[derive(Default, Debug)]
+pub struct VoteAnalyser {
+    state: VoteState,
+    // vote options are announced before the vote is started
+    pending_options: HashMap<u32, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteState {
+    pub votes: Vec<Vote>,
+    pub users: BTreeMap<UserId, UserInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Vote {
+    pub index: u32,
+    pub start_tick: DemoTick,
+    pub end_tick: Option<DemoTick>,
+    /// The team that can vote, `Other` if everyone can vote
+    pub team: Team,
+    /// The player that called the vote, `None` if the vote was started by the server
+    pub initiator: Option<UserId>,
+    pub issue: String,
+    pub details: String,
+    /// The player targeted by the vote, for kick votes
+    pub target: Option<UserId>,
+    pub yes_no: bool,
+    pub options: Vec<String>,
+    pub choices: Vec<VoteChoice>,
+    pub result: VoteResult,
+}
+
+impl Vote {
+    /// The number of players that voted for an option
+    pub fn count(&self, option: u8) -> usize {
+        self.choices
+            .iter()
+            .filter(|choice| choice.option == option)
+            .count()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteChoice {
+    pub user: Option<UserId>,
+    pub option: u8,
+    pub tick: DemoTick,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum VoteResult {
+    /// The demo ended before the vote finished
+    Pending,
+    Passed {
+        issue: String,
+        details: String,
+    },
+    Failed {
+        reason: u8,
+    },
+}
+
+impl MessageHandler for VoteAnalyser {
+    type Output = VoteState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::GameEvent | MessageType::UserMessage
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        match message {
+            Message::GameEvent(message) => self.handle_event(&message.event, tick),
+            Message::UserMessage(message) => self.handle_user_message(message, tick),
+            _ => {}
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        _parser_state: &ParserState,
+    ) {
+        if let Some((_, user_info)) =
+            crate::demo::data::UserInfo::from_string_entry(table, index, entry)
+        {
+            user_info.update_users(&mut self.state.users);
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for VoteAnalyser {
+    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+        &self.state
+    }
+}
+
+impl VoteAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_user_message(&mut self, message: &UserMessage, tick: DemoTick) {
+        match message {
+            UserMessage::VoteStart(message) => self.start_vote(message, tick),
+            UserMessage::VotePass(message) => self.pass_vote(message, tick),
+            UserMessage::VoteFailed(message) => self.fail_vote(message, tick),
+            _ => {}
+        }
+    }
+
+    fn handle_event(&mut self, event: &GameEvent, tick: DemoTick) {
+        match event {
+            GameEvent::VoteOptions(event) => self.set_options(event),
+            GameEvent::VoteCast(event) => self.cast_vote(event, tick),
+            _ => {}
+        }
+    }
+
+    fn start_vote(&mut self, message: &VoteStartMessage, tick: DemoTick) {
+        let options = self
+            .pending_options
+            .remove(&message.vote_index)
+            .unwrap_or_default();
+        let vote = Vote {
+            index: message.vote_index,
+            start_tick: tick,
+            end_tick: None,
+            team: Team::new(message.team),
+            initiator: self.user_id(message.initiator),
+            issue: message.issue.to_string(),
+            details: message.details.to_string(),
+            target: self.user_id(message.target),
+            yes_no: message.yes_no,
+            options,
+            choices: Vec::new(),
+            result: VoteResult::Pending,
+        };
+        self.state.votes.push(vote);
+    }
+
+    fn pass_vote(&mut self, message: &VotePassMessage, tick: DemoTick) {
+        if let Some(vote) = self.active_vote(message.vote_index) {
+            vote.end_tick = Some(tick);
+            vote.result = VoteResult::Passed {
+                issue: message.issue.to_string(),
+                details: message.details.to_string(),
+            };
+        }
+    }
+
+    fn fail_vote(&mut self, message: &VoteFailedMessage, tick: DemoTick) {
+        if let Some(vote) = self.active_vote(message.vote_index) {
+            vote.end_tick = Some(tick);
+            vote.result = VoteResult::Failed {
+                reason: message.reason,
+            };
+        }
+    }
+
+    fn set_options(&mut self, event: &VoteOptionsEvent) {
+        let options: Vec<String> = [
+            &event.option_1,
+            &event.option_2,
+            &event.option_3,
+            &event.option_4,
+            &event.option_5,
+        ]
+        .into_iter()
+        .take(event.count as usize)
+        .map(|option| option.to_string())
+        .collect();
+
+        match self.active_vote(event.voteidx) {
+            Some(vote) => vote.options = options,
+            None => {
+                self.pending_options.insert(event.voteidx, options);
+            }
+        }
+    }
+
+    fn cast_vote(&mut self, event: &VoteCastEvent, tick: DemoTick) {
+        let user = self.user_id(EntityId::from(event.entity_id));
+        if let Some(vote) = self.active_vote(event.voteidx) {
+            vote.choices.push(VoteChoice {
+                user,
+                option: event.vote_option,
+                tick,
+            });
+        }
+    }
+
+    fn active_vote(&mut self, index: u32) -> Option<&mut Vote> {
+        self.state
+            .votes
+            .iter_mut()
+            .rev()
+            .find(|vote| vote.index == index && vote.result == VoteResult::Pending)
+    }
+
+    fn user_id(&self, entity_id: EntityId) -> Option<UserId> {
+        self.state
+            .users
+            .values()
+            .find(|user| user.entity_id == entity_id)
+            .map(|user| user.user_id)
+    }
+}
+
+#[test]
+fn test_vote_timeline() {
+    let state = ParserState::new(24, |_| false, false);
+    let mut analyser = VoteAnalyser::new();
+    for (id, entity) in [(10u16, 3u32), (11, 5), (12, 6)] {
+        let user = crate::demo::data::UserInfo {
+            entity_id: entity.into(),
+            player_info: crate::demo::data::userinfo::PlayerInfo {
+                name: format!("player {}", id),
+                user_id: id.into(),
+                ..Default::default()
+            },
+        };
+        analyser.state.users.insert(id.into(), user.into());
+    }
+
+    analyser.set_options(&VoteOptionsEvent {
+        count: 2,
+        option_1: "Yes".into(),
+        option_2: "No".into(),
+        option_3: "".into(),
+        option_4: "".into(),
+        option_5: "".into(),
+        voteidx: 4,
+    });
+    analyser.handle_user_message(
+        &UserMessage::VoteStart(Box::new(VoteStartMessage {
+            team: 2,
+            vote_index: 4,
+            initiator: 3u32.into(),
+            issue: "#TF_vote_kick_player_other".into(),
+            details: "player 12".into(),
+            yes_no: true,
+            target: 6u32.into(),
+        })),
+        DemoTick::from(100u32),
+    );
+    for (entity, option, tick) in [(3u32, 0u8, 101u32), (5, 1, 105), (6, 1, 110)] {
+        analyser.cast_vote(
+            &VoteCastEvent {
+                vote_option: option,
+                team: 2,
+                entity_id: entity,
+                voteidx: 4,
+            },
+            DemoTick::from(tick),
+        );
+    }
+    analyser.handle_user_message(
+        &UserMessage::VoteFailed(VoteFailedMessage {
+            team: 2,
+            vote_index: 4,
+            reason: 3,
+        }),
+        DemoTick::from(200u32),
+    );
+    // votes for a finished vote are ignored
+    analyser.cast_vote(
+        &VoteCastEvent {
+            vote_option: 0,
+            team: 2,
+            entity_id: 5,
+            voteidx: 4,
+        },
+        DemoTick::from(201u32),
+    );
+
+    let output = analyser.into_output(&state);
+    assert_eq!(1, output.votes.len());
+    let vote = &output.votes[0];
+    assert_eq!(Team::Red, vote.team);
+    assert_eq!(Some(UserId::from(10u16)), vote.initiator);
+    assert_eq!(Some(UserId::from(12u16)), vote.target);
+    assert_eq!(vec!["Yes".to_string(), "No".to_string()], vote.options);
+    assert_eq!(3, vote.choices.len());
+    assert_eq!(1, vote.count(0));
+    assert_eq!(2, vote.count(1));
+    assert_eq!(Some(DemoTick::from(200u32)), vote.end_tick);
+    assert_eq!(VoteResult::Failed { reason: 3 }, vote.result);
+}
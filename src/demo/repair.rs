@@ -0,0 +1,218 @@
+//! Repair demos that weren't closed properly
+//!
+//! When the game crashes or the recording is interrupted, the demo header isn't updated and
+//! the demo can end with a partially written packet.
+
+use crate::demo::data::DemoTick;
+use crate::demo::header::Header;
+use crate::demo::message::setconvar::SetConVarMessage;
+use crate::demo::message::Message;
+use crate::demo::packet::stop::StopPacket;
+use crate::demo::packet::Packet;
+use crate::demo::parser::{DemoHandler, Encode, RawPacketStream};
+use crate::{Demo, Result};
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A header field that was changed while repairing a demo
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+/// The changes made while repairing a demo
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// Number of bytes dropped from the end of the demo because they only contain a partial packet
+    pub dropped_bytes: usize,
+    /// Whether a stop packet was added to the end of the demo
+    pub added_stop: bool,
+    pub ticks: Option<Change<u32>>,
+    pub frames: Option<Change<u32>>,
+    pub duration: Option<Change<f32>>,
+    pub signon: Option<Change<u32>>,
+}
+
+impl RepairReport {
+    /// Whether the demo didn't need any repairs
+    pub fn is_clean(&self) -> bool {
+        self == &RepairReport::default()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return writeln!(f, "demo doesn't need repairs");
+        }
+        if self.dropped_bytes > 0 {
+            writeln!(
+                f,
+                "dropped {} bytes of truncated packet data",
+                self.dropped_bytes
+            )?;
+        }
+        if self.added_stop {
+            writeln!(f, "added missing stop packet")?;
+        }
+        if let Some(Change { from, to }) = self.ticks {
+            writeln!(f, "ticks: {from} -> {to}")?;
+        }
+        if let Some(Change { from, to }) = self.frames {
+            writeln!(f, "frames: {from} -> {to}")?;
+        }
+        if let Some(Change { from, to }) = self.duration {
+            writeln!(f, "duration: {from:.3}s -> {to:.3}s")?;
+        }
+        if let Some(Change { from, to }) = self.signon {
+            writeln!(f, "signon length: {from} -> {to}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The header values as determined from the packets in the demo
+struct PacketScan {
+    ticks: u32,
+    frames: u32,
+    signon: u32,
+    interval_per_tick: f32,
+    /// Position after the last complete packet, in bits from the start of the packet data
+    end: usize,
+    incomplete: bool,
+    last_tick: DemoTick,
+    has_stop: bool,
+}
+
+fn scan_packets(mut packets: RawPacketStream) -> Result<PacketScan> {
+    let mut handler = DemoHandler::default();
+    let mut scan = PacketScan {
+        ticks: 0,
+        frames: 0,
+        signon: 0,
+        interval_per_tick: 0.0,
+        end: 0,
+        incomplete: false,
+        last_tick: DemoTick::default(),
+        has_stop: false,
+    };
+    let mut in_signon = true;
+    let mut tickrate = None;
+
+    while let Some(packet) = packets.next(&handler.state_handler)? {
+        match &packet {
+            Packet::Signon(_) | Packet::DataTables(_) => {}
+            _ => in_signon = false,
+        }
+        if in_signon {
+            scan.signon = (packets.pos() / 8) as u32;
+        }
+
+        match &packet {
+            Packet::Message(_) => scan.frames += 1,
+            Packet::Stop(_) => scan.has_stop = true,
+            _ => {}
+        }
+
+        if let Packet::Signon(message_packet) | Packet::Message(message_packet) = &packet {
+            for message in &message_packet.messages {
+                match message {
+                    Message::ServerInfo(info) => scan.interval_per_tick = info.interval_per_tick,
+                    Message::SetConVar(SetConVarMessage { vars, .. }) => {
+                        for cvar in vars {
+                            if cvar.key == "sv_minupdaterate" {
+                                tickrate = cvar.value.parse::<u32>().ok().filter(|rate| *rate > 0);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        scan.last_tick = packet.tick();
+        scan.end = packets.pos();
+        handler.handle_packet(packet)?;
+    }
+
+    scan.incomplete = packets.incomplete;
+
+    if scan.interval_per_tick <= 0.0 {
+        scan.interval_per_tick = 1.0 / tickrate.unwrap_or(66) as f32;
+    }
+    scan.ticks = scan.last_tick.into();
+
+    Ok(scan)
+}
+
+/// Update the header to match the packet data following it
+///
+/// Returns the changes made to the header
+pub fn fixup_header(header: &mut Header, packets: RawPacketStream) -> Result<RepairReport> {
+    let scan = scan_packets(packets)?;
+    Ok(apply_scan(header, &scan))
+}
+
+fn apply_scan(header: &mut Header, scan: &PacketScan) -> RepairReport {
+    fn change<T: PartialEq + Copy>(field: &mut T, value: T) -> Option<Change<T>> {
+        (*field != value).then(|| {
+            let from = *field;
+            *field = value;
+            Change { from, to: value }
+        })
+    }
+
+    let duration = scan.ticks as f32 * scan.interval_per_tick;
+    RepairReport {
+        ticks: change(&mut header.ticks, scan.ticks),
+        frames: change(&mut header.frames, scan.frames),
+        duration: if (header.duration - duration).abs() > 0.001 {
+            change(&mut header.duration, duration)
+        } else {
+            None
+        },
+        signon: change(&mut header.signon, scan.signon),
+        ..RepairReport::default()
+    }
+}
+
+/// Repair a truncated or unfinished demo
+///
+/// Any trailing partial packet is dropped, a stop packet is added if missing and the header
+/// is updated to match the packet data.
+pub fn repair_demo(demo: &Demo) -> Result<(Vec<u8>, RepairReport)> {
+    let mut stream = demo.get_stream();
+    let mut header = Header::read(&mut stream)?;
+    let packets = RawPacketStream::new(stream.clone());
+
+    let scan = scan_packets(packets)?;
+    let mut report = apply_scan(&mut header, &scan);
+    // any data following the stop packet is kept as is
+    let end = if scan.incomplete {
+        scan.end
+    } else {
+        stream.bits_left()
+    };
+    report.dropped_bytes = (stream.bits_left() - end) / 8;
+    report.added_stop = !scan.has_stop;
+
+    let mut out = Vec::with_capacity(demo.get_stream().bit_len() / 8);
+    {
+        let mut out_stream = BitWriteStream::new(&mut out, LittleEndian);
+        header.write(&mut out_stream)?;
+        let packet_data = stream.read_bits(end)?;
+        packet_data.write(&mut out_stream)?;
+
+        if !scan.has_stop {
+            let handler = DemoHandler::default();
+            Packet::Stop(StopPacket {
+                tick: scan.last_tick,
+            })
+            .encode(&mut out_stream, &handler.state_handler)?;
+        }
+    }
+
+    Ok((out, report))
+}
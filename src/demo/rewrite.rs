@@ -0,0 +1,88 @@
+//! Re-encode a demo packet by packet
+//!
+//! Tools that modify a demo, like [`strip_demo`](crate::demo::strip::strip_demo) and
+//! [`convert_to_stv`](crate::demo::stv::convert_to_stv), parse every packet, change or drop some
+//! of them and encode the result using the parser state of the new demo.
+
+use crate::demo::header::Header;
+use crate::demo::packet::Packet;
+use crate::demo::parser::{DemoHandler, Encode, RawPacketStream};
+use crate::{Demo, ParseError, ParserState, Result};
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+
+/// Parse all packets of a demo
+///
+/// `handle` is called for every packet with the parser state from before the packet and the bit
+/// position of the packet in the demo.
+///
+/// Returns the header of the demo and the number of packets.
+pub(crate) fn walk_packets<'a, E: From<ParseError>>(
+    demo: &Demo<'a>,
+    mut handle: impl FnMut(&Packet<'a>, &ParserState, usize) -> std::result::Result<(), E>,
+) -> std::result::Result<(Header, usize), E> {
+    let mut stream = demo.get_stream();
+    let header = Header::read(&mut stream).map_err(ParseError::from)?;
+    let mut packets = RawPacketStream::new(stream);
+    let mut handler = DemoHandler::default();
+    handler.handle_header(&header);
+
+    let mut count = 0;
+    let mut packet_start = packets.pos();
+    while let Some(packet) = packets.next(&handler.state_handler)? {
+        handle(&packet, &handler.state_handler, packet_start)?;
+        handler.handle_packet(packet)?;
+        count += 1;
+        packet_start = packets.pos();
+    }
+
+    Ok((header, count))
+}
+
+/// Re-encode all packets of a demo
+///
+/// `edit` is called for every packet before it's encoded, with the parser state of the new demo,
+/// it can change the packet or drop it by returning `false`. The sign-on length in the header is
+/// updated to match the new demo.
+///
+/// Returns the new demo and the number of packets written to it.
+pub fn rewrite_demo<'a>(
+    demo: &Demo<'a>,
+    mut edit: impl FnMut(&mut Packet<'a>, &ParserState) -> bool,
+) -> Result<(Vec<u8>, usize)> {
+    let mut encode_handler = DemoHandler::default();
+    encode_handler.handle_header(&Header::read(&mut demo.get_stream())?);
+
+    let mut body = Vec::with_capacity(demo.get_stream().bit_len() / 8);
+    let mut signon = 0;
+    let mut written = 0;
+    let (mut header, _) = {
+        let mut out = BitWriteStream::new(&mut body, LittleEndian);
+        let mut in_signon = true;
+
+        walk_packets(demo, |packet, _, _| -> Result<()> {
+            let mut packet = packet.clone();
+            if !edit(&mut packet, &encode_handler.state_handler) {
+                return Ok(());
+            }
+
+            packet.encode(&mut out, &encode_handler.state_handler)?;
+            written += 1;
+
+            in_signon &= matches!(packet, Packet::Signon(_) | Packet::DataTables(_));
+            if in_signon {
+                signon = out.byte_len();
+            }
+            encode_handler.handle_packet(packet)
+        })?
+    };
+
+    header.signon = signon as u32;
+    let mut out_buffer = Vec::with_capacity(body.len() + 1072);
+    {
+        let mut out = BitWriteStream::new(&mut out_buffer, LittleEndian);
+        header.write(&mut out)?;
+    }
+    out_buffer.extend_from_slice(&body);
+
+    Ok((out_buffer, written))
+}
@@ -1318,6 +1318,38 @@ pub fn write_bit_coord(val: f32, stream: &mut BitWriteStream<LittleEndian>) -> R
     Ok(())
 }
 
+/// Read a vector with a flag for every non-zero coordinate
+pub fn read_bit_vec3_coord(stream: &mut Stream) -> ReadResult<Vector> {
+    let (has_x, has_y, has_z) = stream.read()?;
+
+    Ok(Vector {
+        x: if has_x { read_bit_coord(stream)? } else { 0f32 },
+        y: if has_y { read_bit_coord(stream)? } else { 0f32 },
+        z: if has_z { read_bit_coord(stream)? } else { 0f32 },
+    })
+}
+
+pub fn write_bit_vec3_coord(
+    val: Vector,
+    stream: &mut BitWriteStream<LittleEndian>,
+) -> ReadResult<()> {
+    let has_x = val.x != 0.0;
+    let has_y = val.y != 0.0;
+    let has_z = val.z != 0.0;
+    (has_x, has_y, has_z).write(stream)?;
+
+    if has_x {
+        write_bit_coord(val.x, stream)?;
+    }
+    if has_y {
+        write_bit_coord(val.y, stream)?;
+    }
+    if has_z {
+        write_bit_coord(val.z, stream)?;
+    }
+    Ok(())
+}
+
 #[test]
 fn bit_coord_roundtrip() {
     use bitbuffer::BitReadBuffer;
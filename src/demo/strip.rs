@@ -0,0 +1,145 @@
+//! Remove voice, chat or console data from demos
+//!
+//! The stripped demo is re-encoded with the same packets, minus the removed data, and the header
+//! is updated to match the new sign-on length. The stripped demo is parsed again before it is
+//! returned, to make sure the output is a valid demo.
+
+use crate::demo::message::usermessage::{ChatMessageKind, UserMessage};
+use crate::demo::message::Message;
+use crate::demo::packet::Packet;
+use crate::demo::rewrite::{rewrite_demo, walk_packets};
+use crate::{Demo, ParseError, Result};
+
+/// The data to remove from a demo
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StripOptions {
+    /// Remove `VoiceInit` and `VoiceData` messages
+    pub voice: bool,
+    /// Remove `SayText2` messages of these kinds
+    pub chat: Vec<ChatMessageKind>,
+    /// Remove `ConsoleCmd` packets and `StringCmd` messages
+    pub console: bool,
+}
+
+impl StripOptions {
+    /// Chat kinds that are only visible to the team of the sender
+    pub const TEAM_CHAT: [ChatMessageKind; 2] =
+        [ChatMessageKind::ChatTeam, ChatMessageKind::ChatTeamDead];
+
+    /// Remove voice, team chat and console data
+    pub fn all() -> Self {
+        StripOptions {
+            voice: true,
+            chat: Self::TEAM_CHAT.to_vec(),
+            console: true,
+        }
+    }
+
+    fn keep_packet(&self, packet: &Packet) -> bool {
+        !(self.console && matches!(packet, Packet::ConsoleCmd(_)))
+    }
+
+    fn keep_message(&self, message: &Message) -> bool {
+        match message {
+            Message::VoiceInit(_) | Message::VoiceData(_) => !self.voice,
+            Message::StringCmd(_) => !self.console,
+            Message::UserMessage(UserMessage::SayText2(chat)) => !self.chat.contains(&chat.kind),
+            _ => true,
+        }
+    }
+}
+
+/// Remove the selected data from the demo
+///
+/// Returns an error if the stripped demo can't be parsed back into the packets that were written.
+pub fn strip_demo(demo: &Demo, options: &StripOptions) -> Result<Vec<u8>> {
+    let (out_buffer, packet_count) = rewrite_demo(demo, |packet, _| {
+        if !options.keep_packet(packet) {
+            return false;
+        }
+        if let Packet::Signon(message_packet) | Packet::Message(message_packet) = packet {
+            message_packet
+                .messages
+                .retain(|message| options.keep_message(message));
+        }
+        true
+    })?;
+
+    verify_stripped(&out_buffer, packet_count)?;
+
+    Ok(out_buffer)
+}
+
+/// Parse all packets of the stripped demo and check that all written packets are read back
+fn verify_stripped(data: &[u8], packet_count: usize) -> Result<()> {
+    let (_, parsed) = walk_packets(&Demo::new(data), |_, _, _| Result::Ok(()))?;
+
+    if parsed != packet_count {
+        return Err(ParseError::InvalidDemo(
+            "stripped demo doesn't contain all written packets",
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_keep_chat() {
+    use crate::demo::message::usermessage::SayText2Message;
+
+    let chat = |kind| {
+        Message::UserMessage(UserMessage::SayText2(Box::new(SayText2Message {
+            client: 1u32.into(),
+            raw: 1,
+            kind,
+            from: Some("Player".into()),
+            text: "text".into(),
+        })))
+    };
+    let options = StripOptions {
+        chat: StripOptions::TEAM_CHAT.to_vec(),
+        ..StripOptions::default()
+    };
+    assert!(options.keep_message(&chat(ChatMessageKind::ChatAll)));
+    assert!(options.keep_message(&chat(ChatMessageKind::ChatAllDead)));
+    assert!(!options.keep_message(&chat(ChatMessageKind::ChatTeam)));
+    assert!(!options.keep_message(&chat(ChatMessageKind::ChatTeamDead)));
+    assert!(StripOptions::default().keep_message(&chat(ChatMessageKind::ChatTeam)));
+}
+
+#[test]
+fn test_verify_stripped() {
+    use crate::demo::header::Header;
+    use crate::demo::packet::stop::StopPacket;
+    use crate::demo::parser::{DemoHandler, Encode};
+    use bitbuffer::{BitWrite, BitWriteStream, LittleEndian};
+
+    let mut data = Vec::new();
+    {
+        let mut out = BitWriteStream::new(&mut data, LittleEndian);
+        Header {
+            demo_type: "HL2DEMO".into(),
+            version: 3,
+            protocol: 24,
+            server: "server".into(),
+            nick: "SourceTV".into(),
+            map: "cp_process_final".into(),
+            game: "tf".into(),
+            duration: 0.0,
+            ticks: 0,
+            frames: 0,
+            signon: 0,
+        }
+        .write(&mut out)
+        .unwrap();
+        Packet::Stop(StopPacket { tick: 0u32.into() })
+            .encode(&mut out, &DemoHandler::default().state_handler)
+            .unwrap();
+    }
+
+    assert!(verify_stripped(&data, 1).is_ok());
+    assert!(matches!(
+        verify_stripped(&data, 2),
+        Err(ParseError::InvalidDemo(_))
+    ));
+    assert!(verify_stripped(&data[..data.len() - 1], 1).is_err());
+}
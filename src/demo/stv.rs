@@ -0,0 +1,168 @@
+//! Convert POV demos into STV-style demos
+//!
+//! Some demo viewers only handle demos recorded by SourceTV. POV demos differ from those in a few
+//! ways: they contain the view angles and user commands of the recording player, the local player
+//! receives its position from the "local player exclusive" props and some array props are
+//! sent with a different size.
+//!
+//! The conversion removes the view angles from the packets, marks the demo as STV demo and,
+//! depending on the [`StvOptions`], removes the user commands, moves the local player props
+//! and normalizes the array sizes. Console command packets are always removed.
+
+use crate::demo::message::packetentities::PacketEntitiesMessage;
+use crate::demo::message::Message;
+use crate::demo::packet::datatable::{ParseSendTable, SendTableName};
+use crate::demo::packet::Packet;
+use crate::demo::parser::ParserState;
+use crate::demo::rewrite::rewrite_demo;
+use crate::demo::sendprop::{SendPropIdentifier, SendPropName};
+use crate::{Demo, Result};
+
+/// Props only sent to the recording player, with their counterpart that's sent for all other players
+const LOCAL_PLAYER_PROPS: [(SendPropIdentifier, SendPropIdentifier); 4] = [
+    (
+        SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin"),
+        SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin"),
+    ),
+    (
+        SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin[2]"),
+        SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin[2]"),
+    ),
+    (
+        SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_angEyeAngles[0]"),
+        SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_angEyeAngles[0]"),
+    ),
+    (
+        SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_angEyeAngles[1]"),
+        SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_angEyeAngles[1]"),
+    ),
+];
+
+/// The element count to use for an array prop
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArraySize {
+    pub table: SendTableName,
+    pub prop: SendPropName,
+    pub element_count: u16,
+}
+
+impl ArraySize {
+    pub fn new(
+        table: impl Into<SendTableName>,
+        prop: impl Into<SendPropName>,
+        element_count: u16,
+    ) -> Self {
+        ArraySize {
+            table: table.into(),
+            prop: prop.into(),
+            element_count,
+        }
+    }
+}
+
+/// Options for converting a POV demo into an STV-style demo
+#[derive(Debug, Clone, PartialEq)]
+pub struct StvOptions {
+    /// Keep the `UserCmd` packets of the recording player
+    pub keep_user_cmd: bool,
+    /// Send the position and eye angles of the recording player the same way as for all other players
+    pub fix_local_player_props: bool,
+    /// Array props to resize
+    pub array_sizes: Vec<ArraySize>,
+}
+
+impl Default for StvOptions {
+    fn default() -> Self {
+        StvOptions {
+            keep_user_cmd: false,
+            fix_local_player_props: true,
+            array_sizes: StvOptions::stv_array_sizes(),
+        }
+    }
+}
+
+impl StvOptions {
+    /// The array sizes as used in STV demos
+    pub fn stv_array_sizes() -> Vec<ArraySize> {
+        vec![
+            ArraySize::new("DT_ObjectDispenser", "\"healing_array\"", 101),
+            ArraySize::new("DT_Team", "\"player_array\"", 101),
+            ArraySize::new("DT_TFTeam", "\"team_object_array\"", 606),
+        ]
+    }
+
+    fn resize_arrays(&self, tables: &mut [ParseSendTable]) {
+        for table in tables.iter_mut() {
+            for prop in table.props.iter_mut() {
+                if let Some(size) = self
+                    .array_sizes
+                    .iter()
+                    .find(|size| size.table == table.name && size.prop == prop.name)
+                {
+                    prop.element_count = Some(size.element_count);
+                }
+            }
+        }
+    }
+}
+
+/// Move the local player props to their non-local counterpart
+fn fix_local_player_props(message: &mut PacketEntitiesMessage, state: &ParserState) {
+    for entity in message.entities.iter_mut() {
+        let Some(send_table) = state.send_tables.get(usize::from(entity.server_class)) else {
+            continue;
+        };
+        let existing: Vec<_> = entity.props.iter().map(|prop| prop.identifier).collect();
+        // if the entity already has the non-local prop, the local copy is redundant
+        entity.props.retain(|prop| {
+            !LOCAL_PLAYER_PROPS
+                .iter()
+                .any(|(local, non_local)| *local == prop.identifier && existing.contains(non_local))
+        });
+        for prop in entity.props.iter_mut() {
+            let Some((_, non_local)) = LOCAL_PLAYER_PROPS
+                .iter()
+                .find(|(local, _)| *local == prop.identifier)
+            else {
+                continue;
+            };
+            if let Some(index) = send_table
+                .flattened_props
+                .iter()
+                .position(|definition| definition.identifier == *non_local)
+            {
+                prop.index = index as u32;
+                prop.identifier = *non_local;
+            }
+        }
+    }
+}
+
+/// Convert a POV demo into an STV-style demo
+pub fn convert_to_stv(demo: &Demo, options: &StvOptions) -> Result<Vec<u8>> {
+    let (out_buffer, _) = rewrite_demo(demo, |packet, state| {
+        match packet {
+            Packet::ConsoleCmd(_) => return false,
+            Packet::UserCmd(_) if !options.keep_user_cmd => return false,
+            Packet::DataTables(tables_packet) => {
+                options.resize_arrays(&mut tables_packet.tables);
+            }
+            Packet::Signon(message_packet) | Packet::Message(message_packet) => {
+                message_packet.meta.view_angles = Default::default();
+                for message in message_packet.messages.iter_mut() {
+                    match message {
+                        Message::ServerInfo(info) => info.stv = true,
+                        Message::PacketEntities(entities) if options.fix_local_player_props => {
+                            fix_local_player_props(entities, state)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    })?;
+
+    Ok(out_buffer)
+}
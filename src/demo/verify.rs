@@ -0,0 +1,210 @@
+//! Verify that a demo can be re-encoded without changing its contents
+//!
+//! Every packet is encoded using the parser state at that point in the demo and the encoded data is
+//! parsed again, the result should be identical to the original packet. For message packets
+//! every message is checked separately so a mismatch can be traced back to a single message.
+
+use crate::demo::data::DemoTick;
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::message::MessagePacketMeta;
+use crate::demo::packet::{Packet, PacketType};
+use crate::demo::parser::Encode;
+use crate::demo::rewrite::walk_packets;
+use crate::{Demo, Parse, ParseError, ParserState, Stream};
+use bitbuffer::{BitRead, BitReadBuffer, BitWrite, BitWriteStream, LittleEndian};
+use std::fmt::Debug;
+use thiserror::Error;
+
+/// Errors that can occur while verifying a demo
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("Error while parsing demo: {0}")]
+    Parse(#[from] ParseError),
+    #[error("Roundtrip mismatch in {0}")]
+    Mismatch(Box<RoundtripMismatch>),
+}
+
+/// The first packet or message that isn't the same after encoding and decoding it
+#[derive(Debug, Error)]
+#[error(
+    "{packet_type:?} packet {packet_index} at tick {tick}{}, bit offset {bit_offset}: {kind}",
+    describe_message(.message)
+)]
+pub struct RoundtripMismatch {
+    /// Index of the packet in the demo
+    pub packet_index: usize,
+    pub packet_type: PacketType,
+    pub tick: DemoTick,
+    /// Index and type of the message inside the packet, when the mismatch is in a single message
+    pub message: Option<(usize, MessageType)>,
+    /// Position of the packet or message in the demo, in bits
+    pub bit_offset: usize,
+    pub kind: MismatchKind,
+}
+
+fn describe_message(message: &Option<(usize, MessageType)>) -> String {
+    match message {
+        Some((index, message_type)) => format!(", {message_type:?} message {index}"),
+        None => String::new(),
+    }
+}
+
+/// How the encoded data differs from the original
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum MismatchKind {
+    #[error("failed to encode: {0}")]
+    Encode(#[source] ParseError),
+    #[error("failed to decode the encoded data: {0}")]
+    Decode(#[source] ParseError),
+    #[error("decoded data differs from the original")]
+    Different { original: String, decoded: String },
+    #[error("only {decoded} of the {encoded} encoded bits were used while decoding")]
+    Length { encoded: usize, decoded: usize },
+}
+
+/// Check that all packets in the demo give back the same data after encoding and decoding them
+///
+/// Returns the number of verified packets
+pub fn verify_roundtrip(demo: &Demo) -> Result<usize, VerifyError> {
+    let demo_stream = demo.get_stream();
+    let mut packet_index = 0;
+
+    let (_, packet_count) = walk_packets(
+        demo,
+        |packet, state, packet_start| -> Result<(), VerifyError> {
+            let mismatch = |message, offset, kind| {
+                VerifyError::Mismatch(Box::new(RoundtripMismatch {
+                    packet_index,
+                    packet_type: packet.packet_type(),
+                    tick: packet.tick(),
+                    message,
+                    bit_offset: offset,
+                    kind,
+                }))
+            };
+
+            if matches!(packet, Packet::Signon(_) | Packet::Message(_)) {
+                let mut raw = demo_stream.clone();
+                raw.set_pos(packet_start).map_err(ParseError::from)?;
+                for (index, (offset, message)) in
+                    raw_messages(&mut raw, state)?.into_iter().enumerate()
+                {
+                    roundtrip_message(&message, state).map_err(|kind| {
+                        mismatch(Some((index, message.get_message_type())), offset, kind)
+                    })?;
+                }
+            }
+
+            roundtrip_packet(packet, state).map_err(|kind| mismatch(None, packet_start, kind))?;
+
+            packet_index += 1;
+            Ok(())
+        },
+    )?;
+
+    Ok(packet_count)
+}
+
+/// Read the messages from a message packet, with their position in the packet stream
+fn raw_messages<'a>(
+    stream: &mut Stream<'a>,
+    state: &ParserState,
+) -> Result<Vec<(usize, Message<'a>)>, ParseError> {
+    let _packet_type = PacketType::read(stream)?;
+    let _tick = DemoTick::read(stream)?;
+    let _meta = MessagePacketMeta::read(stream)?;
+    let length: u32 = stream.read()?;
+    let data_start = stream.pos();
+    let mut data = stream.read_bits(length as usize * 8)?;
+
+    let mut messages = Vec::with_capacity(8);
+    while data.bits_left() > 6 {
+        let offset = data_start + data.pos();
+        messages.push((offset, Message::parse(&mut data, state)?));
+    }
+    Ok(messages)
+}
+
+fn roundtrip_message(message: &Message, state: &ParserState) -> Result<(), MismatchKind> {
+    let (data, encoded) = encode(|stream| {
+        message.get_message_type().write(stream)?;
+        message.encode(stream, state)
+    })?;
+    let mut read = Stream::new(BitReadBuffer::new_owned(data, LittleEndian));
+    let decoded = Message::parse(&mut read, state).map_err(MismatchKind::Decode)?;
+    compare(message, &decoded, encoded, read.pos())
+}
+
+fn roundtrip_packet(packet: &Packet, state: &ParserState) -> Result<(), MismatchKind> {
+    let (data, encoded) = encode(|stream| packet.encode(stream, state))?;
+    let mut read = Stream::new(BitReadBuffer::new_owned(data, LittleEndian));
+    let decoded = Packet::parse(&mut read, state).map_err(MismatchKind::Decode)?;
+    compare(packet, &decoded, encoded, read.pos())
+}
+
+fn encode(
+    encode: impl FnOnce(&mut BitWriteStream<LittleEndian>) -> crate::Result<()>,
+) -> Result<(Vec<u8>, usize), MismatchKind> {
+    let mut data = Vec::with_capacity(128);
+    let bits = {
+        let mut stream = BitWriteStream::new(&mut data, LittleEndian);
+        encode(&mut stream).map_err(MismatchKind::Encode)?;
+        stream.bit_len()
+    };
+    Ok((data, bits))
+}
+
+fn compare<T: PartialEq + Debug>(
+    original: &T,
+    decoded: &T,
+    encoded_bits: usize,
+    decoded_bits: usize,
+) -> Result<(), MismatchKind> {
+    if original != decoded {
+        Err(MismatchKind::Different {
+            original: format!("{original:?}"),
+            decoded: format!("{decoded:?}"),
+        })
+    } else if encoded_bits != decoded_bits {
+        Err(MismatchKind::Length {
+            encoded: encoded_bits,
+            decoded: decoded_bits,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mismatch_report() {
+    assert!(compare(&1u8, &1u8, 8, 8).is_ok());
+    assert!(matches!(
+        compare(&1u8, &2u8, 8, 8),
+        Err(MismatchKind::Different { original, decoded }) if original == "1" && decoded == "2"
+    ));
+    assert!(matches!(
+        compare(&1u8, &1u8, 16, 8),
+        Err(MismatchKind::Length {
+            encoded: 16,
+            decoded: 8
+        })
+    ));
+
+    let mismatch = RoundtripMismatch {
+        packet_index: 12,
+        packet_type: PacketType::Message,
+        tick: 5u32.into(),
+        message: Some((3, MessageType::PacketEntities)),
+        bit_offset: 123456,
+        kind: MismatchKind::Length {
+            encoded: 16,
+            decoded: 8,
+        },
+    };
+    assert_eq!(
+        "Message packet 12 at tick 5, PacketEntities message 3, bit offset 123456: only 8 of the 16 encoded bits were used while decoding",
+        mismatch.to_string()
+    );
+}
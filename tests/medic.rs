@@ -0,0 +1,24 @@
+use std::fs;
+
+use tf_demo_parser::demo::parser::gamestateanalyser::GameStateAnalyser;
+use tf_demo_parser::demo::parser::medicanalyser::MedicAnalyser;
+use tf_demo_parser::{Demo, DemoParser};
+
+#[test]
+fn medic_state_without_medics() {
+    let file = fs::read("test_data/short-2024.dem").expect("Unable to read file");
+    let (_, game_state) =
+        DemoParser::new_with_analyser(Demo::new(&file).get_stream(), GameStateAnalyser::new())
+            .parse()
+            .unwrap();
+    assert!(!game_state.players.is_empty());
+    assert!(game_state.mediguns.is_empty());
+
+    let (_, state) =
+        DemoParser::new_with_analyser(Demo::new(&file).get_stream(), MedicAnalyser::new())
+            .parse()
+            .unwrap();
+    assert!(state.medics.is_empty());
+    assert!(state.advantage.is_empty());
+    assert_eq!(0.015, state.interval_per_tick);
+}
@@ -0,0 +1,92 @@
+use bitbuffer::BitRead;
+use std::fs;
+
+use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::merge::{merge_demos, Incompatibility, MergeError};
+use tf_demo_parser::{Demo, DemoParser};
+
+#[test]
+fn merge_same_session() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (header, state) = DemoParser::new(demo.get_stream()).parse().unwrap();
+
+    let merged = merge_demos(&[Demo::new(&file), Demo::new(&file)]).unwrap();
+    let merged_demo = Demo::new(&merged);
+    let (merged_header, merged_state) = DemoParser::new_all(merged_demo.get_stream())
+        .parse()
+        .unwrap();
+
+    assert_eq!(header.map, merged_header.map);
+    assert_eq!(header.frames * 2, merged_header.frames);
+    assert!(merged_header.ticks > header.ticks * 2);
+    assert_eq!(state.chat.len() * 2, merged_state.chat.len());
+    assert_eq!(state.deaths.len() * 2, merged_state.deaths.len());
+    assert_eq!(state.users.len(), merged_state.users.len());
+}
+
+#[test]
+fn merge_different_map() {
+    let small = fs::read("test_data/small.dem").expect("Unable to read file");
+    let mut other = small.clone();
+    // the map name follows the protocols and the 260 byte server and nick fields
+    other[536..796].fill(0);
+    other[536..548].copy_from_slice(b"cp_process_f");
+    let (small_header, small_state) = DemoParser::new(Demo::new(&small).get_stream())
+        .parse()
+        .unwrap();
+    let other_header = Header::read(&mut Demo::new(&other).get_stream()).unwrap();
+    assert_ne!(small_header.map, other_header.map);
+
+    let merged = merge_demos(&[Demo::new(&small), Demo::new(&other)]).unwrap();
+    let merged_demo = Demo::new(&merged);
+    let merged_header = Header::read(&mut merged_demo.get_stream()).unwrap();
+    let (_, merged_state) = DemoParser::new_all(merged_demo.get_stream())
+        .parse()
+        .unwrap();
+
+    assert_eq!(small_header.map, merged_header.map);
+    assert_eq!(small_header.frames * 2, merged_header.frames);
+    assert_eq!(small_state.chat.len() * 2, merged_state.chat.len());
+    assert_eq!(small_state.deaths.len() * 2, merged_state.deaths.len());
+}
+
+#[test]
+fn merge_different_server() {
+    let small = fs::read("test_data/small.dem").expect("Unable to read file");
+    let other = fs::read("test_data/short-2024.dem").expect("Unable to read file");
+
+    // the demos are recorded on different maps, but also with a different number of classes
+    let error = merge_demos(&[Demo::new(&small), Demo::new(&other)]).unwrap_err();
+    match error {
+        MergeError::Incompatible { index, reason } => {
+            assert_eq!(1, index);
+            assert_eq!(Incompatibility::ServerInfo("max_classes"), reason);
+        }
+        error => panic!("unexpected error {error}"),
+    }
+}
+
+#[test]
+fn merge_different_protocol() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let header = Header::read(&mut Demo::new(&file).get_stream()).unwrap();
+    let mut other = file.clone();
+    // the protocol follows the 8 byte magic and 4 byte demo protocol
+    other[12..16].copy_from_slice(&(header.protocol + 1).to_le_bytes());
+
+    let error = merge_demos(&[Demo::new(&file), Demo::new(&other)]).unwrap_err();
+    match error {
+        MergeError::Incompatible { index, reason } => {
+            assert_eq!(1, index);
+            assert_eq!(
+                Incompatibility::Protocol {
+                    expected: header.protocol,
+                    found: header.protocol + 1,
+                },
+                reason
+            );
+        }
+        error => panic!("unexpected error {error}"),
+    }
+}
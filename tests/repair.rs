@@ -0,0 +1,63 @@
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+use std::fs;
+
+use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::repair::{repair_demo, Change};
+use tf_demo_parser::{Demo, DemoParser};
+
+#[test]
+fn repair_intact_demo() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let (repaired, report) = repair_demo(&Demo::new(&file)).unwrap();
+
+    assert!(report.is_clean(), "{report}");
+    assert_eq!(file, repaired);
+}
+
+#[test]
+fn repair_truncated_demo() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let header = Header::read(&mut Demo::new(&file).get_stream()).unwrap();
+
+    // simulate a recording that was interrupted, the header isn't filled in and the last packet is cut off
+    let mut broken = Vec::new();
+    {
+        let mut stream = BitWriteStream::new(&mut broken, LittleEndian);
+        Header {
+            duration: 0.0,
+            ticks: 0,
+            frames: 0,
+            ..header.clone()
+        }
+        .write(&mut stream)
+        .unwrap();
+    }
+    broken.extend_from_slice(&file[broken.len()..file.len() - 1000]);
+
+    let (repaired, report) = repair_demo(&Demo::new(&broken)).unwrap();
+
+    assert!(report.dropped_bytes > 0);
+    assert!(report.added_stop);
+    assert_eq!(None, report.signon);
+    let ticks = report.ticks.unwrap();
+    assert_eq!(0, ticks.from);
+    assert!(ticks.to > 0 && ticks.to <= header.ticks);
+    assert_eq!(0, report.frames.unwrap().from);
+
+    let repaired_header = Header::read(&mut Demo::new(&repaired).get_stream()).unwrap();
+    assert_eq!(ticks.to, repaired_header.ticks);
+    assert_eq!(
+        Some(Change {
+            from: 0.0,
+            to: repaired_header.duration
+        }),
+        report.duration
+    );
+
+    DemoParser::new_all(Demo::new(&repaired).get_stream())
+        .parse()
+        .unwrap();
+
+    let (_, second_report) = repair_demo(&Demo::new(&repaired)).unwrap();
+    assert!(second_report.is_clean(), "{second_report}");
+}
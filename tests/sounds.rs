@@ -0,0 +1,47 @@
+use std::fs;
+
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::message::{Message, MessageType};
+use tf_demo_parser::demo::parser::MessageHandler;
+use tf_demo_parser::{Demo, DemoParser, ParserState};
+
+#[derive(Default)]
+struct SoundNames {
+    sounds: usize,
+    names: Vec<String>,
+}
+
+impl MessageHandler for SoundNames {
+    type Output = (usize, Vec<String>);
+
+    fn does_handle(message_type: MessageType) -> bool {
+        message_type == MessageType::ParseSounds
+    }
+
+    fn handle_message(&mut self, message: &Message, _tick: DemoTick, state: &ParserState) {
+        if let Message::ParseSounds(message) = message {
+            for sound in &message.sounds {
+                self.sounds += 1;
+                if let Some(name) = sound.sound_name(state) {
+                    self.names.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        (self.sounds, self.names)
+    }
+}
+
+#[test]
+fn resolve_sound_names() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let parser = DemoParser::new_with_analyser(demo.get_stream(), SoundNames::default());
+    let (_, (sounds, names)) = parser.parse().unwrap();
+
+    assert!(sounds > 0);
+    assert_eq!(sounds, names.len());
+    assert!(names.iter().all(|name| !name.is_empty()));
+}
@@ -0,0 +1,97 @@
+use bitbuffer::BitRead;
+use std::fs;
+
+use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::message::usermessage::UserMessage;
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::Packet;
+use tf_demo_parser::demo::parser::{DemoHandler, RawPacketStream};
+use tf_demo_parser::demo::strip::{strip_demo, StripOptions};
+use tf_demo_parser::{Demo, DemoParser};
+
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    voice: usize,
+    team_chat: usize,
+    chat: usize,
+    console: usize,
+    string_cmd: usize,
+    messages: usize,
+}
+
+fn count(file: &[u8]) -> Counts {
+    let demo = Demo::new(file);
+    let mut stream = demo.get_stream();
+    let _ = Header::read(&mut stream).unwrap();
+    let mut packets = RawPacketStream::new(stream);
+    let mut handler = DemoHandler::default();
+    let mut counts = Counts::default();
+
+    while let Some(packet) = packets.next(&handler.state_handler).unwrap() {
+        match &packet {
+            Packet::ConsoleCmd(_) => counts.console += 1,
+            Packet::Signon(message_packet) | Packet::Message(message_packet) => {
+                for message in &message_packet.messages {
+                    counts.messages += 1;
+                    match message {
+                        Message::VoiceInit(_) | Message::VoiceData(_) => counts.voice += 1,
+                        Message::StringCmd(_) => {
+                            counts.console += 1;
+                            counts.string_cmd += 1;
+                        }
+                        Message::UserMessage(UserMessage::SayText2(chat)) => {
+                            if StripOptions::TEAM_CHAT.contains(&chat.kind) {
+                                counts.team_chat += 1;
+                            } else {
+                                counts.chat += 1;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        handler.handle_packet(packet).unwrap();
+    }
+    counts
+}
+
+#[test]
+fn strip_nothing() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let stripped = strip_demo(&Demo::new(&file), &StripOptions::default()).unwrap();
+
+    assert_eq!(count(&file), count(&stripped));
+    let header = Header::read(&mut Demo::new(&file).get_stream()).unwrap();
+    let stripped_header = Header::read(&mut Demo::new(&stripped).get_stream()).unwrap();
+    assert_eq!(header.ticks, stripped_header.ticks);
+    assert_eq!(header.frames, stripped_header.frames);
+    assert_eq!(header.duration, stripped_header.duration);
+}
+
+#[test]
+fn strip_all() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let stripped = strip_demo(&Demo::new(&file), &StripOptions::all()).unwrap();
+
+    let original = count(&file);
+    let counts = count(&stripped);
+    assert_eq!(0, counts.voice);
+    assert_eq!(0, counts.team_chat);
+    assert_eq!(0, counts.console);
+    assert_eq!(original.chat, counts.chat);
+    assert_eq!(
+        original.messages - original.voice - original.team_chat - original.string_cmd,
+        counts.messages
+    );
+
+    let (_, state) = DemoParser::new_all(Demo::new(&stripped).get_stream())
+        .parse()
+        .unwrap();
+    let (_, original_state) = DemoParser::new_all(Demo::new(&file).get_stream())
+        .parse()
+        .unwrap();
+    assert_eq!(original_state.deaths, state.deaths);
+    assert_eq!(original_state.users, state.users);
+}
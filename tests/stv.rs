@@ -0,0 +1,119 @@
+use bitbuffer::BitRead;
+use std::fs;
+
+use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::Packet;
+use tf_demo_parser::demo::parser::{DemoHandler, RawPacketStream};
+use tf_demo_parser::demo::sendprop::SendPropIdentifier;
+use tf_demo_parser::demo::stv::{convert_to_stv, StvOptions};
+use tf_demo_parser::{Demo, DemoParser};
+
+const LOCAL_ORIGIN: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin");
+const NON_LOCAL_ORIGIN: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin");
+
+#[derive(Debug, Default)]
+struct Summary {
+    user_cmd: usize,
+    stv: bool,
+    view_angles: bool,
+    local_origin: usize,
+    non_local_origin: usize,
+    healing_array_count: Option<u16>,
+}
+
+fn summarize(file: &[u8]) -> Summary {
+    let demo = Demo::new(file);
+    let mut stream = demo.get_stream();
+    let _ = Header::read(&mut stream).unwrap();
+    let mut packets = RawPacketStream::new(stream);
+    let mut handler = DemoHandler::default();
+    let mut summary = Summary::default();
+
+    while let Some(packet) = packets.next(&handler.state_handler).unwrap() {
+        match &packet {
+            Packet::UserCmd(_) => summary.user_cmd += 1,
+            Packet::DataTables(tables) => {
+                summary.healing_array_count = tables
+                    .tables
+                    .iter()
+                    .filter(|table| table.name == "DT_ObjectDispenser")
+                    .flat_map(|table| table.props.iter())
+                    .find(|prop| prop.name == "\"healing_array\"")
+                    .and_then(|prop| prop.element_count);
+            }
+            Packet::Signon(message_packet) | Packet::Message(message_packet) => {
+                summary.view_angles |= message_packet.meta.view_angles != <[_; 2]>::default();
+                for message in &message_packet.messages {
+                    match message {
+                        Message::ServerInfo(info) => summary.stv = info.stv,
+                        Message::PacketEntities(entities) => {
+                            for prop in entities.entities.iter().flat_map(|e| e.props.iter()) {
+                                if prop.identifier == LOCAL_ORIGIN {
+                                    summary.local_origin += 1;
+                                }
+                                if prop.identifier == NON_LOCAL_ORIGIN {
+                                    summary.non_local_origin += 1;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        handler.handle_packet(packet).unwrap();
+    }
+    summary
+}
+
+#[test]
+fn convert_pov_demo() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let original = summarize(&file);
+    assert!(!original.stv);
+    assert!(original.user_cmd > 0);
+    assert!(original.local_origin > 0);
+
+    let converted = convert_to_stv(&Demo::new(&file), &StvOptions::default()).unwrap();
+    let summary = summarize(&converted);
+    assert!(summary.stv);
+    assert!(!summary.view_angles);
+    assert_eq!(0, summary.user_cmd);
+    assert_eq!(0, summary.local_origin);
+    // local props are moved to the non-local props, unless the non-local prop was also set
+    assert!(summary.non_local_origin > original.non_local_origin);
+    assert!(summary.non_local_origin <= original.non_local_origin + original.local_origin);
+    assert_eq!(Some(101), summary.healing_array_count);
+
+    let (_, state) = DemoParser::new_all(Demo::new(&converted).get_stream())
+        .parse()
+        .unwrap();
+    let (_, original_state) = DemoParser::new_all(Demo::new(&file).get_stream())
+        .parse()
+        .unwrap();
+    assert_eq!(original_state.deaths, state.deaths);
+    assert_eq!(original_state.users, state.users);
+    assert_eq!(original_state.chat, state.chat);
+}
+
+#[test]
+fn convert_keep_pov_data() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let original = summarize(&file);
+
+    let options = StvOptions {
+        keep_user_cmd: true,
+        fix_local_player_props: false,
+        array_sizes: Vec::new(),
+    };
+    let converted = convert_to_stv(&Demo::new(&file), &options).unwrap();
+    let summary = summarize(&converted);
+    assert!(summary.stv);
+    assert_eq!(original.user_cmd, summary.user_cmd);
+    assert_eq!(original.local_origin, summary.local_origin);
+    assert_eq!(original.healing_array_count, summary.healing_array_count);
+}
@@ -0,0 +1,10 @@
+use std::fs;
+
+use tf_demo_parser::demo::verify::verify_roundtrip;
+use tf_demo_parser::Demo;
+
+#[test]
+fn verify_small() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    assert_eq!(591, verify_roundtrip(&Demo::new(&file)).unwrap());
+}